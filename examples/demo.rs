@@ -13,7 +13,7 @@ fn main() {
         start.elapsed()
     );
 
-    let flattened: Vec<_> = json.flatten(u16::MAX as usize).into_iter().collect();
+    let flattened: Vec<_> = json.flatten(u16::MAX as usize).collect();
     let entry_count = flattened.len();
     let mut buf = Vec::new();
     let mut encoder = peon::PrefixEncoder::new(&mut buf);
@@ -33,13 +33,26 @@ fn main() {
         buf.len()
     );
 
-    let mut decoder = peon::PrefixDecoder::new(std::io::Cursor::new(buf));
+    let mut decoder = peon::PrefixDecoder::new(std::io::Cursor::new(&buf));
     let start = Instant::now();
     let mut i = 0;
-    while let Some((path, value)) = decoder.read_next().expect("Failed to read next") {
+    while let Some((_path, _value)) = decoder.read_next().expect("Failed to read next") {
         //println!("{} => {}", path, decode_scalar(value).unwrap());
         i += 1;
     }
     let end = Instant::now();
     println!("read {} entries in {:?}", i, end.duration_since(start));
+
+    let mut decoder = peon::SliceDecoder::new(&buf);
+    let start = Instant::now();
+    let mut i = 0;
+    while let Some((_path, _value)) = decoder.read_next().expect("Failed to read next") {
+        i += 1;
+    }
+    let end = Instant::now();
+    println!(
+        "read {} entries from slice in {:?}",
+        i,
+        end.duration_since(start)
+    );
 }
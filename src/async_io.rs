@@ -0,0 +1,361 @@
+use crate::encoding::body_len;
+use crate::{
+    DecoderLimits, Error, Extension, Path, PathBuf, PrefixDecoder, PrefixEncoder, StreamHeader,
+};
+use futures::{Sink, Stream};
+use std::collections::VecDeque;
+use std::io::Read;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+
+/// Size of the encoder output buffered before it's written to the underlying writer.
+const BUFFER_LEN: usize = 8 * 1024;
+
+/// Encoder writing PEON streams to an [AsyncWrite].
+///
+/// Entries are encoded by a [PrefixEncoder] into a buffer, which is written out once it grows
+/// past a few KiB, or when the encoder is flushed. It also implements a [Sink] of entries,
+/// which writes the end of the stream (see [PrefixEncoder::finish]) and shuts the writer down
+/// when closed.
+pub struct AsyncPrefixEncoder<W> {
+    encoder: PrefixEncoder<Vec<u8>>,
+    writer: W,
+    /// Number of bytes of the buffer already written to `writer`.
+    written: usize,
+    finished: bool,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncPrefixEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self::from_encoder(PrefixEncoder::new(Vec::new()), writer)
+    }
+
+    /// Creates an encoder writing the output of `encoder` to `writer`, e.g. one created by
+    /// [PrefixEncoder::with_header] or configured to write checksums. Anything `encoder` has
+    /// written so far is written to `writer` as well.
+    pub fn from_encoder(encoder: PrefixEncoder<Vec<u8>>, writer: W) -> Self {
+        Self {
+            encoder,
+            writer,
+            written: 0,
+            finished: false,
+        }
+    }
+
+    pub async fn write_next(&mut self, key: &[u8], value: &[u8]) -> std::io::Result<()> {
+        self.encoder.write_next(key, value)?;
+        self.drain_full().await
+    }
+
+    /// Writes an extension entry, see [PrefixEncoder::write_extension].
+    pub async fn write_extension<E: Extension>(&mut self, extension: &E) -> std::io::Result<()> {
+        self.encoder.write_extension(extension)?;
+        self.drain_full().await
+    }
+
+    /// Writes out everything buffered so far and flushes the underlying writer.
+    pub async fn flush(&mut self) -> std::io::Result<()> {
+        std::future::poll_fn(|cx| self.poll_flush_all(cx)).await
+    }
+
+    /// Writes the end of the stream, as [PrefixEncoder::finish] does, flushes the underlying
+    /// writer and returns it.
+    pub async fn finish(mut self) -> std::io::Result<W> {
+        self.encoder.write_tail()?;
+        self.flush().await?;
+        Ok(self.writer)
+    }
+
+    async fn drain_full(&mut self) -> std::io::Result<()> {
+        if self.encoder.writer_mut().len() >= BUFFER_LEN {
+            std::future::poll_fn(|cx| self.poll_drain(cx)).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the whole buffer to the underlying writer.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        let buf = self.encoder.writer_mut();
+        while self.written < buf.len() {
+            let n = ready!(Pin::new(&mut self.writer).poll_write(cx, &buf[self.written..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into()));
+            }
+            self.written += n;
+        }
+        buf.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush_all(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        ready!(self.poll_drain(cx))?;
+        Pin::new(&mut self.writer).poll_flush(cx)
+    }
+}
+
+impl<W, B1, B2> Sink<(PathBuf<B1>, B2)> for AsyncPrefixEncoder<W>
+where
+    W: AsyncWrite + Unpin,
+    B1: AsRef<[u8]>,
+    B2: AsRef<[u8]>,
+{
+    type Error = std::io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if this.encoder.writer_mut().len() >= BUFFER_LEN {
+            this.poll_drain(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: (PathBuf<B1>, B2)) -> Result<(), Self::Error> {
+        let (path, value) = item;
+        self.get_mut()
+            .encoder
+            .write_next(path.as_ref().as_ref(), value.as_ref())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_flush_all(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if !this.finished {
+            this.encoder.write_tail()?;
+            this.finished = true;
+        }
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+/// Items read from the underlying reader, waiting to be decoded.
+///
+/// Only whole items are ever added, so [PrefixDecoder] finds it empty only in between them,
+/// where it can stop and resume once the next item arrives.
+#[derive(Default)]
+struct Pending {
+    buf: VecDeque<u8>,
+    /// Set once the underlying reader is exhausted.
+    closed: bool,
+}
+
+impl Read for Pending {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.buf.is_empty() && !self.closed {
+            return Err(std::io::ErrorKind::WouldBlock.into());
+        }
+        self.buf.read(buf)
+    }
+}
+
+/// Decoder reading PEON streams from an [AsyncRead].
+///
+/// Items of the stream are read one at a time and decoded by a [PrefixDecoder], so that
+/// it validates the stream and enforces [DecoderLimits] the same way.
+pub struct AsyncPrefixDecoder<R> {
+    reader: R,
+    decoder: PrefixDecoder<Pending>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncPrefixDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, DecoderLimits::default())
+    }
+
+    pub fn with_limits(reader: R, limits: DecoderLimits) -> Self {
+        Self {
+            reader,
+            decoder: PrefixDecoder::with_limits(Pending::default(), limits),
+        }
+    }
+
+    /// Makes the decoder skip mandatory extension entries of kind `E` instead of failing, see
+    /// [PrefixDecoder::register_extension].
+    pub fn register_extension<E: Extension>(&mut self) {
+        self.decoder.register_extension::<E>();
+    }
+
+    /// Reads the stream header, see [PrefixDecoder::read_stream_header].
+    pub async fn read_stream_header(&mut self) -> Result<StreamHeader, Error> {
+        let pending = self.decoder.source_mut();
+        if pending.buf.is_empty() && !pending.closed {
+            self.read_item().await?;
+        }
+        self.decoder.read_stream_header()
+    }
+
+    /// Returns the stream header, if the stream started with one.
+    pub fn header(&self) -> Option<StreamHeader> {
+        self.decoder.header()
+    }
+
+    pub async fn read_next(&mut self) -> Result<Option<(Path<'_>, &[u8])>, Error> {
+        let value_len = loop {
+            match self.decoder.read_key() {
+                Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    self.read_item().await?;
+                }
+                res => break res?,
+            }
+        };
+        let Some(value_len) = value_len else {
+            return Ok(None);
+        };
+        self.decoder.read_value(value_len)?;
+        let path = Path::from_slice(self.decoder.last_key());
+        Ok(Some((path, self.decoder.last_value())))
+    }
+
+    /// Turns the decoder into a [Stream] of owned entries, which ends after the first error.
+    pub fn into_stream(self) -> impl Stream<Item = Result<(PathBuf<Vec<u8>>, Vec<u8>), Error>> {
+        futures::stream::unfold(Some(self), |decoder| async move {
+            let mut decoder = decoder?;
+            let entry = match decoder.read_next().await {
+                Ok(Some((path, value))) => (path.as_path_buf(), value.to_vec()),
+                Ok(None) => return None,
+                Err(e) => return Some((Err(e), None)),
+            };
+            Some((Ok(entry), Some(decoder)))
+        })
+    }
+
+    /// Reads the next item of the stream, or what's left of it, into the pending buffer.
+    async fn read_item(&mut self) -> Result<(), Error> {
+        let mut header_buf = [0u8; 6];
+        let len = read_full(&mut self.reader, &mut header_buf).await?;
+        let pending = self.decoder.source_mut();
+        pending.buf.extend(&header_buf[..len]);
+        if len < header_buf.len() {
+            pending.closed = true;
+            return Ok(());
+        }
+
+        let mut body = vec![0u8; body_len(&header_buf)];
+        let len = read_full(&mut self.reader, &mut body).await?;
+        let pending = self.decoder.source_mut();
+        pending.buf.extend(&body[..len]);
+        if len < body.len() {
+            pending.closed = true;
+        }
+        Ok(())
+    }
+}
+
+/// Reads from `reader` until `buf` is full or the reader is exhausted.
+async fn read_full<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]).await? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AsyncPrefixDecoder, AsyncPrefixEncoder};
+    use crate::json::Flatten;
+    use crate::{Error, PathBuf, PrefixDecoder, PrefixEncoder, StreamHeader};
+    use futures::{SinkExt, StreamExt, TryStreamExt};
+
+    fn entries() -> Vec<(PathBuf<Vec<u8>>, Vec<u8>)> {
+        let value: serde_json::Value =
+            serde_json::from_str(include_str!("../assets/complex.json")).unwrap();
+        value
+            .flatten(64)
+            .map(|(path, value)| (path, value.to_vec()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn async_round_trip() {
+        let expected = entries();
+        // small pipe, so that items arrive in pieces
+        let (writer, reader) = tokio::io::duplex(37);
+        let header = StreamHeader::new(StreamHeader::FLAG_CHECKSUMS);
+
+        let write = async {
+            let encoder = PrefixEncoder::with_header(Vec::new(), header)
+                .unwrap()
+                .with_checksums(10);
+            let mut encoder = AsyncPrefixEncoder::from_encoder(encoder, writer);
+            for (path, value) in &expected {
+                encoder.write_next(path.as_bytes(), value).await.unwrap();
+            }
+            encoder.finish().await.unwrap();
+        };
+        let read = async {
+            let mut decoder = AsyncPrefixDecoder::new(reader);
+            assert_eq!(decoder.read_stream_header().await.unwrap(), header);
+            let mut decoded = Vec::new();
+            while let Some((path, value)) = decoder.read_next().await.unwrap() {
+                decoded.push((path.as_path_buf(), value.to_vec()));
+            }
+            decoded
+        };
+        let ((), decoded) = tokio::join!(write, read);
+        assert_eq!(decoded, expected);
+    }
+
+    #[tokio::test]
+    async fn async_sink_and_stream() {
+        let expected = entries();
+        let (writer, reader) = tokio::io::duplex(1024);
+
+        let write = async {
+            // forwarding closes the sink, which finishes the stream
+            let entries = futures::stream::iter(expected.clone()).map(Ok);
+            entries
+                .forward(AsyncPrefixEncoder::new(writer))
+                .await
+                .unwrap();
+        };
+        let read = AsyncPrefixDecoder::new(reader)
+            .into_stream()
+            .try_collect::<Vec<_>>();
+        let ((), decoded) = tokio::join!(write, read);
+        assert_eq!(decoded.unwrap(), expected);
+
+        // same bytes as written by the blocking encoder
+        let mut buf = Vec::new();
+        let mut sink = AsyncPrefixEncoder::new(&mut buf);
+        for (path, value) in &expected {
+            sink.feed((path.clone(), value)).await.unwrap();
+        }
+        SinkExt::<(PathBuf<Vec<u8>>, &Vec<u8>)>::close(&mut sink)
+            .await
+            .unwrap();
+        let mut blocking = PrefixEncoder::new(Vec::new());
+        for (path, value) in &expected {
+            blocking.write_next(path.as_bytes(), value).unwrap();
+        }
+        assert_eq!(buf, blocking.finish().unwrap());
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        assert!(decoder.read_next().unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn async_truncated_stream() {
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        for (path, value) in entries().iter().take(3) {
+            encoder.write_next(path.as_bytes(), value).unwrap();
+        }
+        buf.pop();
+
+        let mut stream = Box::pin(AsyncPrefixDecoder::new(buf.as_slice()).into_stream());
+        assert!(stream.next().await.unwrap().is_ok());
+        assert!(stream.next().await.unwrap().is_ok());
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, Error::Truncated), "{err:?}");
+        assert!(stream.next().await.is_none());
+    }
+}
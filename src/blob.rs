@@ -0,0 +1,211 @@
+use crate::json::TAG_BYTES;
+use crate::{PathBuf, PathSegment, PrefixDecoder};
+use std::io::{Error, ErrorKind, Read};
+
+/// Reader over a bytes value split into continuation entries by
+/// [crate::PrefixEncoder::write_blob].
+///
+/// Consecutive chunks are read from the decoder lazily, one entry at a time. Once the first
+/// entry which doesn't belong to the blob is encountered, it's given back to the decoder, so
+/// the next [PrefixDecoder::read_next] returns it as usual.
+pub struct BlobReader<'a, R> {
+    decoder: &'a mut PrefixDecoder<R>,
+    /// Path of the blob value.
+    path: PathBuf<Vec<u8>>,
+    /// Offset of the next byte within the blob.
+    offset: u64,
+    /// Position of the next byte within the value of the last read entry.
+    pos: usize,
+    done: bool,
+}
+
+impl<'a, R: Read> BlobReader<'a, R> {
+    pub(crate) fn new(decoder: &'a mut PrefixDecoder<R>) -> std::io::Result<Self> {
+        if decoder.last_value().first() != Some(&TAG_BYTES) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "last read entry is not a bytes value",
+            ));
+        }
+        let path = PathBuf::new(decoder.last_key().to_vec());
+        Ok(Self {
+            decoder,
+            path,
+            offset: 0,
+            pos: 1,
+            done: false,
+        })
+    }
+
+    /// Reads the next entry, checking whether it's the next chunk of the blob.
+    fn next_chunk(&mut self) -> std::io::Result<bool> {
+        let Some((path, _)) = self.decoder.read_next()? else {
+            return Ok(false);
+        };
+        let key = path.as_bytes();
+        let base_len = self.path.as_bytes().len();
+        if !key.starts_with(self.path.as_bytes()) || key.len() == base_len {
+            self.decoder.unread();
+            return Ok(false);
+        }
+        let mut segments = crate::path::PathIter::new(&key[base_len..]);
+        match (segments.next(), segments.next(), segments.next()) {
+            (Some(Ok(PathSegment::Index(offset))), Some(Ok(PathSegment::Cont)), None) => {
+                if offset != self.offset {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "expected blob chunk at offset {}, found {offset}",
+                            self.offset
+                        ),
+                    ));
+                }
+                self.pos = 0;
+                Ok(true)
+            }
+            _ => {
+                // entry is nested under the blob path, but it's not a chunk of it
+                self.decoder.unread();
+                Ok(false)
+            }
+        }
+    }
+}
+
+impl<'a, R: Read> Read for BlobReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while !self.done {
+            let chunk = &self.decoder.last_value()[self.pos..];
+            if !chunk.is_empty() {
+                let len = chunk.len().min(buf.len());
+                buf[..len].copy_from_slice(&chunk[..len]);
+                self.pos += len;
+                self.offset += len as u64;
+                return Ok(len);
+            }
+            self.done = !self.next_chunk()?;
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{PathBuf, PathSegment, PrefixDecoder, PrefixEncoder};
+    use std::io::Read;
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn blob_round_trip() {
+        let a = PathBuf::from_iter([PathSegment::Key("a")]);
+        let file = PathBuf::from_iter([PathSegment::Key("file")]);
+        let z = PathBuf::from_iter([PathSegment::Key("z")]);
+        for len in [0, 1, 65533, 65534, 65535, 200_000] {
+            let data = sample(len);
+            let mut buf = Vec::new();
+            let mut encoder = PrefixEncoder::new(&mut buf);
+            encoder.write_next(a.as_ref(), b"a").unwrap();
+            let written = encoder.write_blob(file.as_ref(), data.as_slice()).unwrap();
+            encoder.write_next(z.as_ref(), b"z").unwrap();
+            assert_eq!(written, len as u64);
+
+            let mut decoder = PrefixDecoder::new(buf.as_slice());
+            let (path, _) = decoder.read_next().unwrap().unwrap();
+            assert_eq!(path.as_path_buf(), a);
+            assert!(decoder.blob_reader().is_err());
+            let (path, _) = decoder.read_next().unwrap().unwrap();
+            assert_eq!(path.as_path_buf(), file);
+            assert_eq!(decoder.read_blob().unwrap(), data, "blob of {len} bytes");
+            let (path, value) = decoder.read_next().unwrap().unwrap();
+            assert_eq!(path.as_path_buf(), z);
+            assert_eq!(value, b"z");
+            assert!(decoder.read_next().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn blob_chunks_are_under_64kib() {
+        let file = PathBuf::from_iter([PathSegment::Key("file")]);
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        encoder
+            .write_blob(file.as_ref(), sample(150_000).as_slice())
+            .unwrap();
+
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        let mut entries = Vec::new();
+        while let Some((path, value)) = decoder.read_next().unwrap() {
+            entries.push((path.to_string(), value.len()));
+        }
+        assert_eq!(
+            entries,
+            vec![
+                ("$.file".to_string(), 1),
+                ("$.file[0]..".to_string(), 65535),
+                ("$.file[65535]..".to_string(), 65535),
+                ("$.file[131070]..".to_string(), 18930),
+            ]
+        );
+    }
+
+    #[test]
+    fn blob_inline_boundary() {
+        let file = PathBuf::from_iter([PathSegment::Key("file")]);
+        let entries = |len: usize| {
+            let mut buf = Vec::new();
+            PrefixEncoder::new(&mut buf)
+                .write_blob(file.as_ref(), sample(len).as_slice())
+                .unwrap();
+            let mut decoder = PrefixDecoder::new(buf.as_slice());
+            let mut entries = Vec::new();
+            while let Some((path, value)) = decoder.read_next().unwrap() {
+                entries.push((path.to_string(), value.len()));
+            }
+            entries
+        };
+        // 65534 bytes together with a tag byte make the largest value of a single entry
+        assert_eq!(entries(65534), vec![("$.file".to_string(), 65535)]);
+        assert_eq!(
+            entries(65535),
+            vec![
+                ("$.file".to_string(), 1),
+                ("$.file[0]..".to_string(), 65535),
+            ]
+        );
+        assert_eq!(
+            entries(65536),
+            vec![
+                ("$.file".to_string(), 1),
+                ("$.file[0]..".to_string(), 65535),
+                ("$.file[65535]..".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn blob_reader_streams_small_reads() {
+        let file = PathBuf::from_iter([PathSegment::Key("file")]);
+        let data = sample(100_000);
+        let mut buf = Vec::new();
+        PrefixEncoder::new(&mut buf)
+            .write_blob(file.as_ref(), data.as_slice())
+            .unwrap();
+
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        decoder.read_next().unwrap();
+        let mut reader = decoder.blob_reader().unwrap();
+        let mut actual = Vec::new();
+        let mut small = [0u8; 1000];
+        loop {
+            let n = reader.read(&mut small).unwrap();
+            if n == 0 {
+                break;
+            }
+            actual.extend_from_slice(&small[..n]);
+        }
+        assert_eq!(actual, data);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{Error, Extension};
+use std::io::{Read, Write};
+
+/// Id of the extension entry carrying a checksum of the preceding block of the stream.
+pub(crate) const EXT_CHECKSUM: u16 = 2;
+
+/// Checksum of a block of a PEON stream, written by [crate::PrefixEncoder::with_checksums].
+///
+/// A block spans all bytes following the previous checksum entry (or the stream header, or the
+/// start of the stream) up to the checksum entry itself. Blocks are numbered from 0, so that
+/// a lost or duplicated block is detected as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BlockChecksum {
+    pub block: u64,
+    /// CRC32C of the block.
+    pub crc: u32,
+}
+
+impl Extension for BlockChecksum {
+    const ID: u16 = EXT_CHECKSUM;
+    const OPTIONAL: bool = true;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.block.to_be_bytes());
+        buf.extend_from_slice(&self.crc.to_be_bytes());
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, Error> {
+        let [block @ .., c0, c1, c2, c3] = payload else {
+            return Err(Error::InvalidExtension(Self::ID));
+        };
+        let block = <[u8; 8]>::try_from(block).map_err(|_| Error::InvalidExtension(Self::ID))?;
+        Ok(BlockChecksum {
+            block: u64::from_be_bytes(block),
+            crc: u32::from_be_bytes([*c0, *c1, *c2, *c3]),
+        })
+    }
+}
+
+/// Reader or writer computing CRC32C of the current block of data passing through it.
+pub(crate) struct Crc<T> {
+    pub inner: T,
+    pub enabled: bool,
+    pub crc: u32,
+    /// Number of bytes in the current block.
+    pub len: u64,
+    /// Number of bytes passed through since creation.
+    pub pos: u64,
+}
+
+impl<T> Crc<T> {
+    pub fn new(inner: T, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            crc: 0,
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Starts a new block.
+    pub fn reset(&mut self) {
+        self.crc = 0;
+        self.len = 0;
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.pos += data.len() as u64;
+        if self.enabled {
+            self.crc = crc32c::crc32c_append(self.crc, data);
+            self.len += data.len() as u64;
+        }
+    }
+}
+
+impl<R: Read> Read for Crc<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Crc<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EXT_CHECKSUM;
+    use crate::{Error, PathBuf, PathSegment, PrefixDecoder, PrefixEncoder, StreamHeader};
+
+    fn encode(entries: u64, finish: bool) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let header = StreamHeader::new(StreamHeader::FLAG_CHECKSUMS);
+        let mut encoder = PrefixEncoder::with_header(&mut buf, header)
+            .unwrap()
+            .with_checksums(3);
+        for i in 0..entries {
+            let path = PathBuf::from_iter([PathSegment::Key("items"), i.into()]);
+            encoder
+                .write_next(path.as_ref(), format!("value-{i}").as_bytes())
+                .unwrap();
+        }
+        if finish {
+            encoder.finish().unwrap();
+        }
+        buf
+    }
+
+    fn decode_all(buf: &[u8]) -> Result<Vec<String>, Error> {
+        let mut decoder = PrefixDecoder::new(buf);
+        let mut values = Vec::new();
+        while let Some((_, value)) = decoder.read_next()? {
+            values.push(String::from_utf8(value.to_vec()).unwrap());
+        }
+        Ok(values)
+    }
+
+    fn find(buf: &[u8], needle: &[u8]) -> usize {
+        buf.windows(needle.len()).position(|w| w == needle).unwrap()
+    }
+
+    #[test]
+    fn checksums_round_trip() {
+        for entries in [0, 1, 3, 10] {
+            let buf = encode(entries, true);
+            let expected: Vec<_> = (0..entries).map(|i| format!("value-{i}")).collect();
+            assert_eq!(decode_all(&buf).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn checksums_detect_corrupted_block() {
+        let buf = encode(10, true);
+        for (needle, block) in [(&b"value-1"[..], 0), (b"value-7", 2), (b"value-9", 3)] {
+            let mut corrupted = buf.clone();
+            corrupted[find(&buf, needle) + 6] ^= 0b0100;
+            let res = decode_all(&corrupted);
+            assert!(
+                matches!(res, Err(Error::ChecksumMismatch { block: b }) if b == block),
+                "{res:?}"
+            );
+        }
+
+        // block lost in transfer
+        let checksum_header = [0x80, 0, 0, 12, 0x80, EXT_CHECKSUM as u8];
+        let checksums: Vec<_> = buf
+            .windows(6)
+            .enumerate()
+            .filter(|(_, w)| *w == checksum_header)
+            .map(|(pos, _)| pos + 6 + 12)
+            .collect();
+        let mut lost = buf[..checksums[0]].to_vec();
+        lost.extend_from_slice(&buf[checksums[1]..]);
+        let res = decode_all(&lost);
+        assert!(
+            matches!(res, Err(Error::ChecksumMismatch { block: 1 })),
+            "{res:?}"
+        );
+    }
+
+    #[test]
+    fn checksums_required_by_header() {
+        let buf = encode(4, false);
+        let res = decode_all(&buf);
+        assert!(
+            matches!(res, Err(Error::MissingChecksum { block: 1 })),
+            "{res:?}"
+        );
+    }
+}
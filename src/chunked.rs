@@ -0,0 +1,269 @@
+use crate::json::{TAG_BYTES, TAG_STRING};
+use crate::{Path, PrefixDecoder};
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+
+/// Seekable reader over a string or bytes value, which may be split into continuation entries
+/// (`path[offset]..`).
+///
+/// When created, entries following the value are scanned once for its continuation entries,
+/// skipping over their contents, to find out where each chunk is stored in the underlying
+/// stream. Afterwards, reads go straight to the chunk covering the current position, so that
+/// any byte range of a large value can be served without reading the rest of it.
+///
+/// Once the reader is dropped, the decoder is positioned right after the last chunk of the
+/// value, so the next [PrefixDecoder::read_next] returns the entry which follows it.
+pub struct ChunkedValueReader<'a, R: Read + Seek> {
+    decoder: &'a mut PrefixDecoder<R>,
+    chunks: Vec<Chunk>,
+    /// Length of the whole value.
+    len: u64,
+    /// Position within the value.
+    pos: u64,
+    /// Current position of the underlying stream.
+    stream_pos: u64,
+    /// Position of the underlying stream right after the last chunk of the value.
+    end: u64,
+}
+
+fn compressed_error() -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        "values in compressed blocks can't be read in chunks",
+    )
+}
+
+struct Chunk {
+    /// Offset of the chunk within the value.
+    offset: u64,
+    /// Position of the chunk data within the underlying stream.
+    start: u64,
+    len: u64,
+}
+
+impl<'a, R: Read + Seek> ChunkedValueReader<'a, R> {
+    pub(crate) fn new(decoder: &'a mut PrefixDecoder<R>) -> std::io::Result<Self> {
+        if decoder.in_block() {
+            return Err(compressed_error());
+        }
+        let key = decoder.last_key().to_vec();
+        let value = decoder.last_value();
+        let (base_len, data_start) = match Path::from_slice(&key).split_chunk() {
+            Some((base_len, 0)) => (base_len, 0),
+            Some((_, offset)) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "last read entry is a chunk at offset {offset}, not a start of the value"
+                    ),
+                ));
+            }
+            None => match value.first() {
+                // value tag is not a part of the data
+                Some(&TAG_STRING) | Some(&TAG_BYTES) => (key.len(), 1),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        "last read entry is not a string or bytes value",
+                    ));
+                }
+            },
+        };
+
+        let mut len = (value.len() - data_start) as u64;
+        let mut end = decoder.reader_mut().stream_position()?;
+        let mut chunks = vec![Chunk {
+            offset: 0,
+            start: end - len,
+            len,
+        }];
+        let mut last_key = key;
+        while let Some(value_len) = decoder.read_key()? {
+            let path = Path::from_slice(decoder.last_key());
+            match path.split_chunk() {
+                Some((chunk_base_len, offset))
+                    if chunk_base_len == base_len
+                        && path.as_bytes().starts_with(&last_key[..base_len]) =>
+                {
+                    if decoder.in_block() {
+                        return Err(compressed_error());
+                    }
+                    if offset != len {
+                        return Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("expected chunk at offset {len}, found {offset}"),
+                        ));
+                    }
+                    last_key.clear();
+                    last_key.extend_from_slice(path.as_bytes());
+                    let reader = decoder.reader_mut();
+                    let start = reader.stream_position()?;
+                    end = reader.seek(SeekFrom::Current(value_len as i64))?;
+                    chunks.push(Chunk {
+                        offset,
+                        start,
+                        len: value_len as u64,
+                    });
+                    len += value_len as u64;
+                }
+                _ => break,
+            }
+        }
+        // give back the entry following the value, if we've read it
+        decoder.seek_reader(end)?;
+        decoder.reset_last(&last_key);
+
+        Ok(Self {
+            decoder,
+            chunks,
+            len,
+            pos: 0,
+            stream_pos: end,
+            end,
+        })
+    }
+
+    /// Returns the length of the whole value.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<'a, R: Read + Seek> Read for ChunkedValueReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.pos >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let i = self
+            .chunks
+            .partition_point(|chunk| chunk.offset + chunk.len <= self.pos);
+        let chunk = &self.chunks[i];
+        let skip = self.pos - chunk.offset;
+        let len = (chunk.len - skip).min(buf.len() as u64) as usize;
+        let reader = self.decoder.reader_mut();
+        if self.stream_pos != chunk.start + skip {
+            self.stream_pos = reader.seek(SeekFrom::Start(chunk.start + skip))?;
+        }
+        reader.read_exact(&mut buf[..len])?;
+        self.stream_pos += len as u64;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for ChunkedValueReader<'a, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let (base, delta) = match pos {
+            SeekFrom::Start(pos) => (pos, 0),
+            SeekFrom::End(delta) => (self.len, delta),
+            SeekFrom::Current(delta) => (self.pos, delta),
+        };
+        match base.checked_add_signed(delta) {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Drop for ChunkedValueReader<'a, R> {
+    fn drop(&mut self) {
+        if self.stream_pos != self.end {
+            // if this fails, the next read from the decoder will fail as well
+            let _ = self.decoder.reader_mut().seek(SeekFrom::Start(self.end));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json::Flatten;
+    use crate::{PathBuf, PathSegment, PrefixDecoder, PrefixEncoder};
+    use serde_json::json;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    fn read_range<R: Read + Seek>(reader: &mut R, start: u64, len: usize) -> Vec<u8> {
+        reader.seek(SeekFrom::Start(start)).unwrap();
+        let mut buf = vec![0u8; len];
+        reader.read_exact(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn chunked_string_ranges() {
+        let text: String = (0..500).map(|i| format!("{i:04}")).collect();
+        let source = json!({ "a": 1, "text": text, "z": "end" });
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        for (path, value) in source.flatten(64) {
+            encoder.write_next(path.as_bytes(), &value).unwrap();
+        }
+
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf));
+        let (path, _) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.to_string(), "$.a");
+        assert!(decoder.chunked_value_reader().is_err());
+        let (path, _) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.to_string(), "$.text[0]..");
+
+        let mut reader = decoder.chunked_value_reader().unwrap();
+        assert_eq!(reader.len(), text.len() as u64);
+        for (start, len) in [(0, 10), (60, 10), (1000, 200), (1990, 10), (64, 64)] {
+            let actual = read_range(&mut reader, start as u64, len);
+            assert_eq!(
+                actual,
+                &text.as_bytes()[start..start + len],
+                "{start}+{len}"
+            );
+        }
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        let mut tail = String::new();
+        reader.read_to_string(&mut tail).unwrap();
+        assert_eq!(tail, "0499");
+        assert!(reader.seek(SeekFrom::Current(-10_000)).is_err());
+        drop(reader);
+
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.to_string(), "$.z");
+        assert_eq!(&value[1..], b"end");
+        assert!(decoder.read_next().unwrap().is_none());
+    }
+
+    #[test]
+    fn chunked_blob_ranges() {
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let file = PathBuf::from_iter([PathSegment::Key("file")]);
+        let files = PathBuf::from_iter([PathSegment::Key("file"), PathSegment::Index(0)]);
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        encoder.write_blob(file.as_ref(), data.as_slice()).unwrap();
+        encoder.write_next(files.as_ref(), b"x").unwrap();
+
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf));
+        decoder.read_next().unwrap();
+        let mut reader = decoder.chunked_value_reader().unwrap();
+        assert_eq!(reader.len(), data.len() as u64);
+        // ranges crossing chunk boundaries, read in reverse order
+        for start in [199_000, 131_000, 65_000, 0] {
+            let actual = read_range(&mut reader, start as u64, 1000);
+            assert_eq!(actual, &data[start..start + 1000], "{start}");
+        }
+        let mut all = Vec::new();
+        reader.rewind().unwrap();
+        reader.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+        drop(reader);
+
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.as_path_buf(), files);
+        assert_eq!(value, b"x");
+    }
+}
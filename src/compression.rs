@@ -0,0 +1,270 @@
+use crate::Error;
+use crate::checksum::Crc;
+use std::io::Read;
+
+/// Id of the extension entry carrying a compressed block of entries. Blocks are mandatory, so
+/// that decoders which don't support them fail instead of silently skipping the entries.
+pub(crate) const EXT_COMPRESSED: u16 = 6;
+
+/// Maximum length of an uncompressed block, such that compressed blocks always fit into
+/// a single extension entry.
+pub(crate) const MAX_BLOCK_LEN: usize = 60 * 1024;
+
+/// Block stored as is, when compressing it doesn't pay off.
+const CODEC_STORED: u8 = 0;
+
+/// Block compressed with LZ4 block format.
+#[cfg(feature = "lz4")]
+const CODEC_LZ4: u8 = 1;
+
+/// Compresses a block of encoded entries into the payload of a compressed block entry, which
+/// holds the codec, the length of the uncompressed block and the compressed data.
+pub(crate) fn compress(block: &[u8], payload: &mut Vec<u8>) {
+    debug_assert!(block.len() <= MAX_BLOCK_LEN);
+    payload.clear();
+    payload.push(CODEC_STORED);
+    payload.extend_from_slice(&(block.len() as u16).to_be_bytes());
+    #[cfg(feature = "lz4")]
+    {
+        payload.resize(3 + lz4_flex::block::get_maximum_output_size(block.len()), 0);
+        if let Ok(len) = lz4_flex::block::compress_into(block, &mut payload[3..])
+            && len < block.len()
+        {
+            payload[0] = CODEC_LZ4;
+            payload.truncate(3 + len);
+            return;
+        }
+        payload.truncate(3);
+    }
+    payload.extend_from_slice(block);
+}
+
+/// Decompresses the payload of a compressed block entry into `block`.
+pub(crate) fn decompress(payload: &[u8], block: &mut Vec<u8>) -> Result<(), Error> {
+    let [codec, len_hi, len_lo, data @ ..] = payload else {
+        return Err(Error::InvalidExtension(EXT_COMPRESSED));
+    };
+    let len = u16::from_be_bytes([*len_hi, *len_lo]) as usize;
+    block.clear();
+    match *codec {
+        CODEC_STORED if data.len() == len => block.extend_from_slice(data),
+        #[cfg(feature = "lz4")]
+        CODEC_LZ4 => {
+            block.resize(len, 0);
+            match lz4_flex::block::decompress_into(data, block) {
+                Ok(decompressed) if decompressed == len => {}
+                _ => return Err(Error::InvalidExtension(EXT_COMPRESSED)),
+            }
+        }
+        CODEC_STORED => return Err(Error::InvalidExtension(EXT_COMPRESSED)),
+        codec => return Err(Error::UnsupportedCompression(codec)),
+    }
+    Ok(())
+}
+
+/// Input of a decoder, which reads items from the current decompressed block, if there is one,
+/// or from the stream.
+pub(crate) struct Input<R> {
+    pub stream: Crc<R>,
+    /// Decompressed block of entries.
+    pub block: Vec<u8>,
+    /// Position within the block.
+    pub block_pos: usize,
+    /// Position of the block entry within the stream.
+    pub block_start: u64,
+    /// Whether the current item is read from the block. Items never span block boundaries.
+    pub in_block: bool,
+}
+
+impl<R> Input<R> {
+    pub fn new(stream: Crc<R>) -> Self {
+        Self {
+            stream,
+            block: Vec::new(),
+            block_pos: 0,
+            block_start: 0,
+            in_block: false,
+        }
+    }
+
+    /// Starts reading the next item, from the block if anything is left of it.
+    pub fn next_item(&mut self) {
+        self.in_block = self.block_pos < self.block.len();
+    }
+
+    /// Drops the rest of the current block, e.g. after the stream has been repositioned.
+    pub fn clear_block(&mut self) {
+        self.block.clear();
+        self.block_pos = 0;
+        self.in_block = false;
+    }
+}
+
+impl<R: Read> Read for Input<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.in_block {
+            let n = (&self.block[self.block_pos..]).read(buf)?;
+            self.block_pos += n;
+            Ok(n)
+        } else {
+            self.stream.read(buf)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "lz4"))]
+mod test {
+    use super::{EXT_COMPRESSED, MAX_BLOCK_LEN, compress, decompress};
+    use crate::json::Flatten;
+    use crate::{Error, PathBuf, PathSegment, PrefixDecoder, PrefixEncoder, StreamHeader};
+    use std::io::Cursor;
+
+    fn encode(value: &serde_json::Value, block_size: Option<usize>) -> Vec<u8> {
+        let mut header = StreamHeader::default();
+        if block_size.is_some() {
+            header.flags |= StreamHeader::FLAG_COMPRESSION;
+        }
+        let mut encoder = PrefixEncoder::with_header(Vec::new(), header).unwrap();
+        if let Some(block_size) = block_size {
+            encoder = encoder.with_compression(block_size);
+        }
+        for (path, value) in value.clone().flatten(100) {
+            encoder.write_next(path.as_bytes(), &value).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn decode(buf: &[u8]) -> Result<Vec<(String, Vec<u8>)>, Error> {
+        let mut decoder = PrefixDecoder::new(buf);
+        let mut entries = Vec::new();
+        while let Some((path, value)) = decoder.read_next()? {
+            entries.push((path.to_string(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    #[test]
+    fn compression_ratio() {
+        let value: serde_json::Value =
+            serde_json::from_str(include_str!("../assets/complex.json")).unwrap();
+        let plain = encode(&value, None);
+        let expected = decode(&plain).unwrap();
+        for block_size in [256, 4096, MAX_BLOCK_LEN] {
+            let compressed = encode(&value, Some(block_size));
+            assert_eq!(decode(&compressed).unwrap(), expected, "{block_size}");
+            if block_size == MAX_BLOCK_LEN {
+                // whole document fits into a single block
+                assert!(
+                    compressed.len() * 10 < plain.len() * 7,
+                    "compressed {} bytes vs plain {} bytes",
+                    compressed.len(),
+                    plain.len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn compression_with_restarts_and_checksums() {
+        let header =
+            StreamHeader::new(StreamHeader::FLAG_CHECKSUMS | StreamHeader::FLAG_COMPRESSION);
+        let mut encoder = PrefixEncoder::with_header(Cursor::new(Vec::new()), header)
+            .unwrap()
+            .with_index(50)
+            .with_checksums(30)
+            .with_compression(1024);
+        let user = |i: u64| PathBuf::from_iter([PathSegment::Key("users"), i.into()]);
+        let large = vec![b'x'; 2000];
+        for i in 0..1000 {
+            // large values don't fit into blocks and are written as they are
+            let value = if i % 100 == 0 {
+                &large
+            } else {
+                b"user".as_slice()
+            };
+            encoder.write_next(user(i).as_ref(), value).unwrap();
+        }
+        let buf = encoder.finish().unwrap().into_inner();
+        assert_eq!(decode(&buf).unwrap().len(), 1000);
+
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf.as_slice()));
+        decoder.seek_to(&user(777).as_path()).unwrap();
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.as_path_buf(), user(777));
+        assert_eq!(value, b"user");
+        let err = decoder.chunked_value_reader().err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+        decoder.seek_to(&user(900).as_path()).unwrap();
+        let (_, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(value, large);
+    }
+
+    #[test]
+    fn compression_with_restarts_without_index() {
+        let header = StreamHeader::new(StreamHeader::FLAG_COMPRESSION);
+        let mut encoder = PrefixEncoder::with_header(Vec::new(), header)
+            .unwrap()
+            .with_restart_interval(10)
+            .with_compression(1024);
+        let user = |i: u64| PathBuf::from_iter([PathSegment::Key("users"), i.into()]);
+        for i in 0..100 {
+            encoder
+                .write_next(user(i).as_ref(), format!("user-{i}").as_bytes())
+                .unwrap();
+        }
+        let buf = encoder.finish().unwrap();
+
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf.as_slice()));
+        let first = decoder.seek_to_restart(0).unwrap().unwrap();
+        let mut count = 0;
+        while decoder.read_next().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 100);
+
+        // restart points are found behind compressed blocks
+        decoder.seek_to_restart(first + 1).unwrap().unwrap();
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.as_path_buf(), user(10));
+        assert_eq!(value, b"user-10");
+    }
+
+    #[test]
+    fn compression_malformed_blocks() {
+        let block = b"some block of entries".repeat(10);
+        let mut payload = Vec::new();
+        compress(&block, &mut payload);
+        let mut decompressed = Vec::new();
+        decompress(&payload, &mut decompressed).unwrap();
+        assert_eq!(decompressed, block);
+
+        // wrong length, truncated data and unknown codec
+        let mut wrong_len = payload.clone();
+        wrong_len[2] += 1;
+        for (payload, expected) in [
+            (wrong_len, "malformed payload of extension entry: 6"),
+            (
+                payload[..payload.len() - 1].to_vec(),
+                "malformed payload of extension entry: 6",
+            ),
+            (vec![7, 0, 0], "unsupported compression codec: 7"),
+        ] {
+            let err = decompress(&payload, &mut decompressed).unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+
+        // blocks of unknown codecs fail decoding
+        let unknown = [0x80, 0, 0, 3, 0, EXT_COMPRESSED as u8, 7, 0, 0];
+        assert!(matches!(
+            decode(&unknown),
+            Err(Error::UnsupportedCompression(7))
+        ));
+
+        // entries can't span beyond the end of a block, here a stored block with its last byte
+        // moved out of it
+        let mut stored = vec![0x80, 0, 0, 15, 0, EXT_COMPRESSED as u8, 0, 0, 12];
+        stored.extend_from_slice(&[0, 2, 0, 5, 0, 0, 0, b'a', b'v', b'a', b'l', b'u']);
+        stored.push(b'e');
+        assert!(matches!(decode(&stored), Err(Error::Truncated)));
+    }
+}
@@ -0,0 +1,163 @@
+use crate::path::PathIter;
+use crate::{Error, Path, PrefixDecoder};
+use std::io::Read;
+
+/// Cursor over the entries of a stream, created by [PrefixDecoder::cursor].
+///
+/// Unlike [PrefixDecoder::read_next], moving the cursor and looking at the current entry are
+/// separate steps. On top of that, it can jump past all entries under a path, deciding which
+/// entries to skip by the length of the prefix they share with the previous key, without
+/// parsing their paths or reading their values.
+pub struct DecoderCursor<'a, R> {
+    decoder: &'a mut PrefixDecoder<R>,
+    /// Set while the cursor points at an entry.
+    valid: bool,
+}
+
+impl<'a, R: Read> DecoderCursor<'a, R> {
+    pub(crate) fn new(decoder: &'a mut PrefixDecoder<R>) -> Self {
+        Self {
+            decoder,
+            valid: false,
+        }
+    }
+
+    /// Moves the cursor to the next entry. Returns `false` once there are no more entries.
+    pub fn advance(&mut self) -> Result<bool, Error> {
+        self.valid = false;
+        self.valid = self.decoder.read_next()?.is_some();
+        Ok(self.valid)
+    }
+
+    /// Returns the encoded path of the current entry, or `None` if the cursor doesn't point at
+    /// any.
+    pub fn key(&self) -> Option<&[u8]> {
+        self.valid.then(|| self.decoder.last_key())
+    }
+
+    pub fn value(&self) -> Option<&[u8]> {
+        self.valid.then(|| self.decoder.last_value())
+    }
+
+    pub fn path(&self) -> Option<Path<'_>> {
+        self.key().map(Path::from_slice)
+    }
+
+    /// Skips the subtree of the current entry, i.e. moves the cursor to the first following
+    /// entry which isn't at or under its path, skipping continuation entries of its value too,
+    /// e.g. from `$.users[0].name` to `$.users[0].age`. Moves to the first entry if the cursor
+    /// doesn't point at any yet.
+    pub fn skip_subtree(&mut self) -> Result<bool, Error> {
+        if !self.valid {
+            return self.advance();
+        }
+        let len = self.decoder.last_key().len();
+        self.skip_under(len)
+    }
+
+    /// Skips the rest of the object or array containing the current entry, i.e. moves the
+    /// cursor to the first following entry which isn't under its parent path, e.g. from
+    /// `$.users[0].name` to `$.users[1].name`. Moves to the first entry if the cursor doesn't
+    /// point at any yet.
+    pub fn skip_to_parent_sibling(&mut self) -> Result<bool, Error> {
+        if !self.valid {
+            return self.advance();
+        }
+        let mut segments = PathIter::new(self.decoder.last_key());
+        let mut parent_len = 0;
+        loop {
+            let start = segments.offset();
+            match segments.next() {
+                Some(segment) => {
+                    segment?;
+                    parent_len = start;
+                }
+                None => break,
+            }
+        }
+        self.skip_under(parent_len)
+    }
+
+    fn skip_under(&mut self, len: usize) -> Result<bool, Error> {
+        self.valid = false;
+        if let Some(value_len) = self.decoder.skip_under(len)? {
+            self.decoder.read_value(value_len)?;
+            self.valid = true;
+        }
+        Ok(self.valid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{PathBuf, PathSegment, PrefixDecoder, PrefixEncoder};
+
+    fn user(index: u64, field: &str) -> PathBuf<Vec<u8>> {
+        PathBuf::from_iter([PathSegment::Key("users"), index.into(), field.into()])
+    }
+
+    fn sample(restart_interval: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        if restart_interval > 0 {
+            encoder = encoder.with_restart_interval(restart_interval);
+        }
+        let admin = PathBuf::from_iter([PathSegment::Key("user")]);
+        encoder.write_next(admin.as_ref(), b"admin").unwrap();
+        for i in 0..3 {
+            encoder
+                .write_next(user(i, "name").as_ref(), format!("name-{i}").as_bytes())
+                .unwrap();
+            if i == 1 {
+                // split into continuation entries
+                let avatar = vec![7u8; 200_000];
+                encoder
+                    .write_blob(user(i, "avatar").as_ref(), avatar.as_slice())
+                    .unwrap();
+            }
+            encoder
+                .write_next(user(i, "age").as_ref(), format!("age-{i}").as_bytes())
+                .unwrap();
+        }
+        let count = PathBuf::from_iter([PathSegment::Key("usersCount")]);
+        encoder.write_next(count.as_ref(), b"3").unwrap();
+        encoder.finish().unwrap();
+        buf
+    }
+
+    #[test]
+    fn cursor_navigation() {
+        for restart_interval in [0, 1, 3] {
+            let buf = sample(restart_interval);
+            let mut decoder = PrefixDecoder::new(buf.as_slice());
+            let mut cursor = decoder.cursor();
+            assert_eq!(cursor.key(), None);
+
+            // without a current entry, cursor moves to the first one
+            assert!(cursor.skip_subtree().unwrap());
+            assert_eq!(cursor.value(), Some(b"admin".as_slice()));
+            // `$.user` is a prefix of `$.users` bytes, but not of its path
+            assert!(cursor.skip_subtree().unwrap());
+            assert_eq!(cursor.path().unwrap().as_path_buf(), user(0, "name"));
+            assert_eq!(cursor.value(), Some(b"name-0".as_slice()));
+            assert!(cursor.skip_subtree().unwrap());
+            assert_eq!(cursor.value(), Some(b"age-0".as_slice()));
+            assert!(cursor.skip_to_parent_sibling().unwrap());
+            assert_eq!(cursor.value(), Some(b"name-1".as_slice()));
+
+            // chunks of the avatar are under its path
+            assert!(cursor.skip_subtree().unwrap());
+            assert_eq!(cursor.key(), Some(user(1, "avatar").as_bytes()));
+            assert!(cursor.skip_subtree().unwrap());
+            assert_eq!(cursor.value(), Some(b"age-1".as_slice()));
+
+            assert!(cursor.advance().unwrap());
+            assert!(cursor.skip_to_parent_sibling().unwrap());
+            assert_eq!(cursor.value(), Some(b"3".as_slice()));
+            // parent of top level entries is the root, so the rest of the stream is skipped
+            assert!(!cursor.skip_to_parent_sibling().unwrap());
+            assert_eq!(cursor.value(), None);
+            assert!(!cursor.advance().unwrap());
+        }
+    }
+}
@@ -0,0 +1,839 @@
+use crate::json::scalar::decode_decimal;
+use crate::json::{DecodeError, ScalarRef, TAG_BYTES, TAG_DECIMAL, TAG_NULL, TAG_STRING};
+use crate::path::{PathError, PathIter};
+use crate::{PathSegment, PrefixDecoder};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use std::fmt::Display;
+use std::io::Read;
+
+/// Deserializes an instance of `T` from a stream of PEON entries.
+///
+/// Fails if the stream contains entries that don't belong to the deserialized value.
+pub fn from_reader<R, T>(reader: R) -> Result<T, Error>
+where
+    R: Read,
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(PrefixDecoder::new(reader));
+    let value = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Deserializes an instance of `T` from a byte slice of PEON entries.
+pub fn from_slice<T>(slice: &[u8]) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    from_reader(slice)
+}
+
+/// Serde deserializer reading values straight from the entries of [PrefixDecoder], without
+/// materializing an intermediate `serde_json::Value`.
+///
+/// Entries are expected in the order produced by [crate::json::Flatten] or
+/// [crate::ser::Serializer]: all entries of the same object or array must be adjacent.
+/// Streams don't need to be complete - entries missing from the stream (eg. filtered out by
+/// [crate::JsonPath]) are treated as absent fields, and missing array indexes are skipped.
+pub struct Deserializer<R> {
+    decoder: PrefixDecoder<R>,
+    entry: Entry,
+    state: State,
+    /// Number of path segments of the value that is currently being deserialized.
+    depth: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum State {
+    /// Current entry has been consumed, next one must be read from the decoder.
+    Consumed,
+    /// Current entry has been read from the decoder, but not consumed yet.
+    Ready,
+    /// Decoder has no more entries.
+    Eof,
+}
+
+impl<R: Read> Deserializer<R> {
+    pub fn new(decoder: PrefixDecoder<R>) -> Self {
+        Self {
+            decoder,
+            entry: Entry::default(),
+            state: State::Consumed,
+            depth: 0,
+        }
+    }
+
+    /// Makes sure that all entries of the underlying stream have been consumed.
+    pub fn end(&mut self) -> Result<(), Error> {
+        match self.peek()? {
+            None => Ok(()),
+            Some(_) => Err(Error::TrailingEntries),
+        }
+    }
+
+    pub fn into_inner(self) -> PrefixDecoder<R> {
+        self.decoder
+    }
+
+    fn peek(&mut self) -> Result<Option<&Entry>, Error> {
+        if self.state == State::Consumed {
+            match self.decoder.read_next()? {
+                Some((path, value)) => {
+                    self.entry.fill(path.as_bytes(), value)?;
+                    self.state = State::Ready;
+                }
+                None => self.state = State::Eof,
+            }
+        }
+        match self.state {
+            State::Ready => Ok(Some(&self.entry)),
+            _ => Ok(None),
+        }
+    }
+
+    fn consume(&mut self) {
+        if self.state == State::Ready {
+            self.state = State::Consumed;
+        }
+    }
+
+    /// Returns a shape of the value at the current depth, or `None` if the stream has ended.
+    fn peek_shape(&mut self) -> Result<Option<Shape>, Error> {
+        let depth = self.depth;
+        match self.peek()? {
+            None => Ok(None),
+            Some(entry) => entry.shape(depth).map(Some),
+        }
+    }
+
+    /// Returns path of the container at the current depth.
+    fn container_prefix(&self) -> Vec<u8> {
+        self.entry.key[..self.entry.bounds[self.depth]].to_vec()
+    }
+
+    /// Reassembles a string split into continuation entries.
+    fn read_chunked_str(&mut self) -> Result<String, Error> {
+        let prefix = self.container_prefix();
+        let buf = self.read_chunks(&prefix, Vec::new())?;
+        String::from_utf8(buf).map_err(|e| Error::InvalidUtf8(e.utf8_error()))
+    }
+
+    /// Appends continuation entries of a value at `prefix` to `buf`.
+    fn read_chunks(&mut self, prefix: &[u8], mut buf: Vec<u8>) -> Result<Vec<u8>, Error> {
+        let depth = self.depth;
+        while let Some(entry) = self.peek()? {
+            if !entry.is_under(prefix, depth) || entry.shape(depth)? != Shape::Chunks {
+                break;
+            }
+            match entry.segment(depth)? {
+                PathSegment::Index(offset) if offset == buf.len() as u64 => {
+                    buf.extend_from_slice(&entry.value);
+                }
+                _ => return Err(Error::InvalidChunk(prefix.len())),
+            }
+            self.consume();
+        }
+        Ok(buf)
+    }
+
+    /// Skips all remaining entries under the `prefix` of a container at given `depth`.
+    fn drain(&mut self, prefix: &[u8], depth: usize) -> Result<(), Error> {
+        while let Some(entry) = self.peek()? {
+            if !entry.is_under(prefix, depth) {
+                break;
+            }
+            self.consume();
+        }
+        Ok(())
+    }
+
+    fn visit_scalar<'de, V: Visitor<'de>>(&mut self, visitor: V) -> Result<V::Value, Error> {
+        self.consume();
+        if self.entry.value.first() == Some(&TAG_BYTES) {
+            // bytes value may be followed by continuation entries, see `write_blob`
+            let prefix = self.entry.key.clone();
+            let buf = self.entry.value[1..].to_vec();
+            return visitor.visit_byte_buf(self.read_chunks(&prefix, buf)?);
+        }
+        match ScalarRef::decode(&self.entry.value)? {
+            ScalarRef::Null => visitor.visit_unit(),
+            ScalarRef::Bool(value) => visitor.visit_bool(value),
+            ScalarRef::Int(value) => visitor.visit_i64(value),
+            ScalarRef::UInt(value) => visitor.visit_u64(value),
+            ScalarRef::Float(value) => visitor.visit_f64(value),
+            // decimals are exact only when deserialized as strings, see `deserialize_str`
+            ScalarRef::Decimal(value) => match value.parse() {
+                Ok(number) => visitor.visit_f64(number),
+                Err(_) => Err(DecodeError::InvalidDecimal.into()),
+            },
+            ScalarRef::Str(value) => visitor.visit_str(value),
+            ScalarRef::Bytes(value) | ScalarRef::Chunk(value) => visitor.visit_bytes(value),
+            ScalarRef::EmptyObject => visitor.visit_map(de::value::MapDeserializer::new(
+                std::iter::empty::<((), ())>(),
+            )),
+            ScalarRef::EmptyArray => {
+                visitor.visit_seq(de::value::SeqDeserializer::new(std::iter::empty::<()>()))
+            }
+        }
+    }
+
+    fn visit_container<'de, V: Visitor<'de>>(
+        &mut self,
+        shape: Shape,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let depth = self.depth;
+        let prefix = self.container_prefix();
+        let mut access = Access {
+            de: self,
+            prefix,
+            depth,
+        };
+        let value = match shape {
+            Shape::Object => visitor.visit_map(&mut access)?,
+            _ => visitor.visit_seq(&mut access)?,
+        };
+        // skip over entries that visitor was not interested in
+        let prefix = access.prefix;
+        self.drain(&prefix, depth)?;
+        Ok(value)
+    }
+
+    /// Deserializes a value nested one level deeper than the current one.
+    fn nested<'de, T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<T::Value, Error> {
+        self.depth += 1;
+        let result = seed.deserialize(&mut *self);
+        self.depth -= 1;
+        result
+    }
+}
+
+impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek_shape()? {
+            None => Err(Error::Eof),
+            Some(Shape::Scalar) => self.visit_scalar(visitor),
+            Some(Shape::Chunks) => visitor.visit_string(self.read_chunked_str()?),
+            Some(shape) => self.visit_container(shape, visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek_shape()? {
+            Some(Shape::Scalar) if self.entry.value.first() == Some(&TAG_DECIMAL) => {
+                self.consume();
+                visitor.visit_str(decode_decimal(&self.entry.value)?)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek_shape()? {
+            Some(Shape::Scalar) if self.entry.value.first() == Some(&TAG_STRING) => {
+                self.consume();
+                visitor.visit_bytes(&self.entry.value[1..])
+            }
+            Some(Shape::Chunks) => {
+                let prefix = self.container_prefix();
+                visitor.visit_byte_buf(self.read_chunks(&prefix, Vec::new())?)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.peek_shape()? {
+            None => visitor.visit_none(),
+            Some(Shape::Scalar) if self.entry.value.as_slice() == [TAG_NULL] => {
+                self.consume();
+                visitor.visit_none()
+            }
+            Some(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.peek_shape()? {
+            Some(Shape::Scalar) if self.entry.value.first() == Some(&TAG_STRING) => {
+                self.consume();
+                let variant = std::str::from_utf8(&self.entry.value[1..])?;
+                visitor.visit_enum(variant.into_deserializer())
+            }
+            Some(Shape::Chunks) => visitor.visit_enum(self.read_chunked_str()?.into_deserializer()),
+            Some(Shape::Object) => {
+                let depth = self.depth;
+                let prefix = self.container_prefix();
+                let variant = match self.entry.segment(depth)? {
+                    PathSegment::Key(key) => key.to_string(),
+                    _ => unreachable!("object entries always start with key segment"),
+                };
+                let value = visitor.visit_enum(Enum { de: self, variant })?;
+                self.drain(&prefix, depth)?;
+                Ok(value)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Accessor for the members of objects and elements of arrays.
+struct Access<'a, R> {
+    de: &'a mut Deserializer<R>,
+    /// Path of the container.
+    prefix: Vec<u8>,
+    /// Number of segments in the container path.
+    depth: usize,
+}
+
+impl<'a, R: Read> Access<'a, R> {
+    /// Returns the path segment of the next member of the container, if there is any.
+    fn next_member(&mut self) -> Result<Option<PathSegment<'_>>, Error> {
+        match self.de.peek()? {
+            Some(entry) if entry.is_under(&self.prefix, self.depth) => {
+                if entry.bounds.len() == self.depth {
+                    // entry refers to the container itself, which is not a valid member
+                    Err(Error::UnexpectedScalar(self.prefix.len()))
+                } else {
+                    entry.segment(self.depth).map(Some)
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<'de, 'a, R: Read> de::MapAccess<'de> for Access<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        match self.next_member()? {
+            None => Ok(None),
+            Some(PathSegment::Key(key)) => seed.deserialize(MapKey(key)).map(Some),
+            Some(segment) => Err(Error::UnexpectedSegment(segment.to_string())),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        self.de.nested(seed)
+    }
+}
+
+impl<'de, 'a, R: Read> de::SeqAccess<'de> for Access<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.next_member()? {
+            None => Ok(None),
+            Some(PathSegment::Index(_)) => self.de.nested(seed).map(Some),
+            Some(segment) => Err(Error::UnexpectedSegment(segment.to_string())),
+        }
+    }
+}
+
+/// Accessor for enum variants carrying data, represented as single-key objects.
+struct Enum<'a, R> {
+    de: &'a mut Deserializer<R>,
+    variant: String,
+}
+
+impl<'de, 'a, R: Read> de::EnumAccess<'de> for Enum<'a, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let deserializer: de::value::StrDeserializer<Error> =
+            self.variant.as_str().into_deserializer();
+        let variant = seed.deserialize(deserializer)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a, R: Read> de::VariantAccess<'de> for Enum<'a, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.de.nested(std::marker::PhantomData::<de::IgnoredAny>)?;
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        self.de.nested(seed)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.de.nested(AnySeed(visitor))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.de.nested(AnySeed(visitor))
+    }
+}
+
+/// Adapter passing a visitor of a compound enum variant through [Deserializer::nested].
+struct AnySeed<V>(V);
+
+impl<'de, V: Visitor<'de>> DeserializeSeed<'de> for AnySeed<V> {
+    type Value = V::Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        deserializer.deserialize_any(self.0)
+    }
+}
+
+/// Deserializer for object keys. Just like in JSON, keys are always strings, but they can be
+/// parsed back into numbers and booleans when the deserialized type expects them.
+struct MapKey<'a>(&'a str);
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident),*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                match self.0.parse() {
+                    Ok(value) => visitor.$visit(value),
+                    Err(_) => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for MapKey<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_str(self.0)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 f32 f64 char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Owned copy of the entry read from the decoder.
+#[derive(Debug, Default)]
+struct Entry {
+    key: Vec<u8>,
+    value: Vec<u8>,
+    /// Byte offsets at which the consecutive path segments of the key start.
+    bounds: Vec<usize>,
+}
+
+impl Entry {
+    fn fill(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.key.clear();
+        self.key.extend_from_slice(key);
+        self.value.clear();
+        self.value.extend_from_slice(value);
+        self.bounds.clear();
+        let mut iter = PathIter::new(key);
+        loop {
+            let offset = iter.offset();
+            match iter.next() {
+                None => break,
+                Some(segment) => {
+                    segment?;
+                    self.bounds.push(offset);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn segment(&self, index: usize) -> Result<PathSegment<'_>, Error> {
+        match PathIter::new(&self.key[self.bounds[index]..]).next() {
+            Some(segment) => Ok(segment?),
+            None => Err(PathError::Eof.into()),
+        }
+    }
+
+    /// Checks if this entry belongs to a container at a given `prefix` path with `depth` segments.
+    fn is_under(&self, prefix: &[u8], depth: usize) -> bool {
+        let container_len = if depth == self.bounds.len() {
+            self.key.len()
+        } else {
+            self.bounds.get(depth).copied().unwrap_or(usize::MAX)
+        };
+        container_len == prefix.len() && self.key.starts_with(prefix)
+    }
+
+    fn shape(&self, depth: usize) -> Result<Shape, Error> {
+        let segments = self.bounds.len();
+        if segments == depth {
+            return Ok(Shape::Scalar);
+        }
+        if segments == depth + 2 && self.segment(depth + 1)? == PathSegment::Cont {
+            return Ok(Shape::Chunks);
+        }
+        match self.segment(depth)? {
+            PathSegment::Key(_) => Ok(Shape::Object),
+            PathSegment::Index(_) => Ok(Shape::Array),
+            PathSegment::Cont => Err(Error::UnexpectedSegment(PathSegment::Cont.to_string())),
+        }
+    }
+}
+
+/// Shape of the value determined by the first entry describing it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Shape {
+    /// Value is stored entirely in a single entry.
+    Scalar,
+    /// Value is a string split into continuation entries.
+    Chunks,
+    /// Value is an object with entries for each of its fields.
+    Object,
+    /// Value is an array with entries for each of its elements.
+    Array,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read entry: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid entry path: {0}")]
+    Path(#[from] PathError),
+    #[error("unexpected end of PEON stream")]
+    Eof,
+    #[error("PEON stream contains entries outside of deserialized value")]
+    TrailingEntries,
+    #[error("invalid value: {0}")]
+    InvalidValue(#[from] DecodeError),
+    #[error("invalid utf-8 string: {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+    #[error("continuation entries are not in order (path length: {0})")]
+    InvalidChunk(usize),
+    #[error("container value mixed with scalar (path length: {0})")]
+    UnexpectedScalar(usize),
+    #[error("unexpected path segment: {0}")]
+    UnexpectedSegment(String),
+    #[error("malformed PEON stream: {0}")]
+    Malformed(crate::Error),
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl From<crate::Error> for Error {
+    fn from(e: crate::Error) -> Self {
+        match e {
+            crate::Error::Io(e) => Error::Io(e),
+            crate::Error::InvalidPath(e) => Error::Path(e),
+            crate::Error::InvalidValue(e) => Error::InvalidValue(e),
+            e => Error::Malformed(e),
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::JsonPath;
+    use crate::json::Flatten;
+    use crate::path::Encode;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+        age: u32,
+        nick: Option<String>,
+        friends: Vec<Friend>,
+        role: Role,
+        scores: BTreeMap<u32, f64>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Friend {
+        name: Option<String>,
+        nick: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Role {
+        Admin,
+        Guest { since: i64 },
+        Banned(String, u8),
+    }
+
+    fn users() -> Vec<User> {
+        vec![
+            User {
+                name: "Alice".into(),
+                age: 25,
+                nick: None,
+                friends: vec![
+                    Friend {
+                        name: Some("Bob".into()),
+                        nick: "boreas".into(),
+                    },
+                    Friend {
+                        name: None,
+                        nick: "crocodile91".into(),
+                    },
+                ],
+                role: Role::Admin,
+                scores: BTreeMap::from([(1, 0.5), (20, -1.25)]),
+            },
+            User {
+                name: "Bob".into(),
+                age: 30,
+                nick: Some("boreas".into()),
+                friends: vec![],
+                role: Role::Guest { since: -120 },
+                scores: BTreeMap::new(),
+            },
+            User {
+                name: "Damian".into(),
+                age: 35,
+                nick: None,
+                friends: vec![],
+                role: Role::Banned("spam".into(), 3),
+                scores: BTreeMap::new(),
+            },
+        ]
+    }
+
+    #[test]
+    fn serialize_deserialize() {
+        let expected = users();
+        let buf = crate::ser::to_vec(&expected, 100).unwrap();
+        let actual: Vec<User> = super::from_slice(&buf).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn deserialize_flattened() {
+        let expected = users();
+        let mut buf = Vec::new();
+        serde_json::to_value(&expected)
+            .unwrap()
+            .flatten(100)
+            .write_to(&mut buf)
+            .unwrap();
+        let actual: Vec<User> = super::from_slice(&buf).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn deserialize_chunked_string() {
+        let expected = BTreeMap::from([
+            ("a".to_string(), "lorem ipsum dolor sit amet ".repeat(10)),
+            ("b".to_string(), "short".to_string()),
+        ]);
+        let buf = crate::ser::to_vec(&expected, 40).unwrap();
+        let actual: BTreeMap<String, String> = super::from_slice(&buf).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn deserialize_scalar_root() {
+        let buf = crate::ser::to_vec(&-42i64, 100).unwrap();
+        let actual: i64 = super::from_slice(&buf).unwrap();
+        assert_eq!(actual, -42);
+    }
+
+    #[test]
+    fn deserialize_filtered() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Root {
+            users: Vec<Named>,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Named {
+            name: Option<String>,
+        }
+
+        let json_path = JsonPath::parse("users[*].name").unwrap();
+        let mut buf = Vec::new();
+        json!({
+            "users": [
+                { "name": "Alice", "age": 25 },
+                { "name": "Bob", "age": 30 },
+                { "nick": "crocodile91", "age": 35 },
+                { "name": "Damian", "age": 30 },
+            ]
+        })
+        .flatten(100)
+        .filter(|(path, _)| json_path.is_match(&path.as_path()))
+        .write_to(&mut buf)
+        .unwrap();
+
+        let actual: Root = super::from_slice(&buf).unwrap();
+        let name = |name: &str| Named {
+            name: Some(name.into()),
+        };
+        assert_eq!(
+            actual,
+            Root {
+                users: vec![name("Alice"), name("Bob"), name("Damian")]
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_ignores_unknown_fields() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Short {
+            age: u32,
+        }
+        let buf = crate::ser::to_vec(&users(), 100).unwrap();
+        let actual: Vec<Short> = super::from_slice(&buf).unwrap();
+        assert_eq!(
+            actual,
+            vec![Short { age: 25 }, Short { age: 30 }, Short { age: 35 }]
+        );
+    }
+
+    #[test]
+    fn deserialize_trailing_entries() {
+        let mut buf = crate::ser::to_vec(&1u32, 100).unwrap();
+        buf.extend(crate::ser::to_vec(&2u32, 100).unwrap());
+        let res = super::from_slice::<u32>(&buf);
+        assert!(matches!(res, Err(super::Error::TrailingEntries)));
+    }
+
+    #[test]
+    fn deserialize_decimals() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Price {
+            exact: String,
+            approx: f64,
+        }
+        let mut buf = Vec::new();
+        let mut encoder = crate::PrefixEncoder::new(&mut buf);
+        let decimal = crate::json::scalar::encode_decimal("1.10");
+        encoder
+            .write_next(&[0, b'a', b'p', b'p', b'r', b'o', b'x'], &decimal)
+            .unwrap();
+        encoder
+            .write_next(&[0, b'e', b'x', b'a', b'c', b't'], &decimal)
+            .unwrap();
+        let actual: Price = super::from_slice(&buf).unwrap();
+        assert_eq!(
+            actual,
+            Price {
+                exact: "1.10".into(),
+                approx: 1.1
+            }
+        );
+    }
+
+    #[test]
+    fn deserialize_blobs() {
+        #[derive(Debug, PartialEq)]
+        struct Bytes(Vec<u8>);
+
+        impl Serialize for Bytes {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(&self.0)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for Bytes {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct BytesVisitor;
+                impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                    type Value = Bytes;
+                    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                        f.write_str("bytes")
+                    }
+                    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                        Ok(Bytes(v))
+                    }
+                }
+                deserializer.deserialize_byte_buf(BytesVisitor)
+            }
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct File {
+            content: Bytes,
+            name: String,
+        }
+
+        for len in [0, 10, 200_000] {
+            let file = File {
+                content: Bytes((0..len).map(|i| (i % 251) as u8).collect()),
+                name: "image.png".into(),
+            };
+            let buf = crate::ser::to_vec(&file, 100).unwrap();
+            let actual: File = super::from_slice(&buf).unwrap();
+            assert_eq!(actual, file);
+        }
+    }
+}
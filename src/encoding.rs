@@ -1,55 +1,406 @@
-use crate::Path;
-use std::io::{Read, Write};
+use crate::checksum::{BlockChecksum, Crc, EXT_CHECKSUM};
+use crate::compression::{EXT_COMPRESSED, Input, compress, decompress};
+use crate::header::EXT_HEADER;
+use crate::index::{
+    EXT_INDEX, EXT_INDEX_TRAILER, IndexTrailer, RESTART_MARKER, RestartPoint, TRAILER_LEN,
+    decode_index, encode_index,
+};
+use crate::json::TAG_BYTES;
+use crate::path::is_segment_boundary;
+use crate::{
+    BlobReader, ChunkedValueReader, DecodedItem, DecoderCursor, Error, Extension, ExtensionEntry,
+    JsonPath, MIN_USER_ID, Path, PathBuf, StreamHeader,
+};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::iter;
 
 pub struct PrefixEncoder<W> {
     last_key: Vec<u8>,
-    writer: W,
+    writer: Crc<W>,
+    /// Number of entries per checksummed block, 0 if checksums are disabled.
+    checksum_interval: u64,
+    /// Number of entries written in the current block.
+    block_entries: u64,
+    /// Index of the current block.
+    block: u64,
+    /// Number of entries between restart points, 0 if they're disabled.
+    restart_interval: u64,
+    /// Number of entries written since the last restart point.
+    restart_entries: u64,
+    /// Restart points written so far, if the index is enabled.
+    index: Option<Vec<RestartPoint>>,
+    /// Maximum length of a compressed block, 0 if compression is disabled.
+    compression_block: usize,
+    /// Entries waiting to be compressed.
+    pending: Vec<u8>,
+    /// Header written at the start of the stream, if any.
+    header: Option<StreamHeader>,
 }
 
 impl<W> PrefixEncoder<W> {
     pub fn new(writer: W) -> Self {
         Self {
             last_key: Vec::new(),
-            writer,
+            writer: Crc::new(writer, false),
+            checksum_interval: 0,
+            block_entries: 0,
+            block: 0,
+            restart_interval: 0,
+            restart_entries: 0,
+            index: None,
+            compression_block: 0,
+            pending: Vec::new(),
+            header: None,
         }
     }
+
+    /// Makes the encoder follow every `interval` entries with a checksum of all bytes written
+    /// since the previous checksum, so that decoders can detect corrupted blocks of the stream.
+    /// Must be called before any entries are written.
+    ///
+    /// Remember to call [PrefixEncoder::finish] to checksum the last block. Set
+    /// [StreamHeader::FLAG_CHECKSUMS] in the stream header to make decoders require them.
+    pub fn with_checksums(mut self, interval: u64) -> Self {
+        assert!(interval > 0, "checksum interval must be positive");
+        self.checksum_interval = interval;
+        self.writer.enabled = true;
+        self.writer.reset();
+        self
+    }
+
+    /// Makes the encoder write every `interval`-th entry as a restart point, with its full key
+    /// instead of a delta from the previous one, preceded by a sync marker. Decoding can resume
+    /// from any restart point, see [PrefixDecoder::seek_to_restart]. Must be called before any
+    /// entries are written.
+    pub fn with_restart_interval(mut self, interval: u64) -> Self {
+        assert!(interval > 0, "restart interval must be positive");
+        self.restart_interval = interval;
+        self
+    }
+
+    /// Makes the encoder write restart points every `interval` entries, as
+    /// [PrefixEncoder::with_restart_interval] does, and keep an index of them. The index is
+    /// written at the end of the stream by [PrefixEncoder::finish], so that
+    /// [PrefixDecoder::seek_to] can jump close to any path without decoding the stream from its
+    /// start.
+    ///
+    /// Entries must be written ordered by their paths. Memory used by the index grows with the
+    /// number of restart points. Must be called before any entries are written.
+    pub fn with_index(mut self, interval: u64) -> Self {
+        self = self.with_restart_interval(interval);
+        self.index = Some(Vec::new());
+        self
+    }
+
+    /// Makes the encoder group entries into blocks of up to `block_size` bytes, written as
+    /// single extension entries compressed with LZ4. Larger blocks compress better, while
+    /// smaller ones need less buffering. Entries which don't fit into a block on their own,
+    /// restart points, checksums and other extension entries of PEON itself are written
+    /// uncompressed.
+    ///
+    /// Decoders without compression support fail on the first block with
+    /// [Error::UnsupportedExtension] or [Error::UnsupportedCompression]. Remember to call
+    /// [PrefixEncoder::finish] to write the last block. Must be called before any entries are
+    /// written. If the stream has a header, it must have [StreamHeader::FLAG_COMPRESSION] set.
+    #[cfg(feature = "lz4")]
+    pub fn with_compression(mut self, block_size: usize) -> Self {
+        use crate::compression::MAX_BLOCK_LEN;
+        assert!(
+            block_size > 0 && block_size <= MAX_BLOCK_LEN,
+            "compression block size must be between 1 and {MAX_BLOCK_LEN}"
+        );
+        assert!(
+            self.header
+                .is_none_or(|h| h.has_flag(StreamHeader::FLAG_COMPRESSION)),
+            "stream header of a compressed stream must have FLAG_COMPRESSION set"
+        );
+        self.compression_block = block_size;
+        self
+    }
 }
 
 /// Maximum allowed length of a path is 32KiB.
-const MAX_PATH_LEN: usize = 0x0111_1111_1111_1111;
+pub(crate) const MAX_PATH_LEN: usize = 0x7FFF;
+
+/// Maximum length of a single chunk of blob data, limited by the maximum length of a value.
+pub(crate) const MAX_CHUNK_LEN: usize = u16::MAX as usize;
 
 /// Special bit to indicate that the entry is using extension format.
 /// Extension format is reserved to the future use, but current decoder needs to be aware of it
 /// in order to correctly decode the entries.
-const EXT_ENTRY: u8 = 0b1000_0000;
+pub(crate) const EXT_ENTRY: u8 = 0b1000_0000;
 
 impl<W: Write> PrefixEncoder<W> {
+    /// Creates an encoder which starts the stream with a `header`, so that decoders can
+    /// recognize it, see [PrefixDecoder::read_stream_header].
+    pub fn with_header(writer: W, header: StreamHeader) -> std::io::Result<Self> {
+        let mut encoder = Self::new(writer);
+        encoder.write_builtin(&header)?;
+        encoder.header = Some(header);
+        Ok(encoder)
+    }
+
+    /// Writes an extension entry. Extension entries have no key, carry the id of their kind in
+    /// place of the prefix length and don't affect prefix compression of the entries around
+    /// them.
+    ///
+    /// Fails with [std::io::ErrorKind::InvalidInput] if the id of `E` is outside of the range
+    /// of user-defined extensions, from [MIN_USER_ID] to `0x7FFF`.
+    pub fn write_extension<E: Extension>(&mut self, extension: &E) -> std::io::Result<()> {
+        if E::ID < MIN_USER_ID || E::ID as usize > MAX_PATH_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "extension id {} is outside of user range {MIN_USER_ID}..={MAX_PATH_LEN}",
+                    E::ID
+                ),
+            ));
+        }
+        let mut payload = Vec::new();
+        extension.encode(&mut payload);
+        self.write_ext(E::ID, E::OPTIONAL, &payload, true)
+    }
+
+    /// Writes an extension entry defined by PEON itself. These are framing, so they're never
+    /// compressed.
+    pub(crate) fn write_builtin<E: Extension>(&mut self, extension: &E) -> std::io::Result<()> {
+        let mut payload = Vec::new();
+        extension.encode(&mut payload);
+        self.write_ext(E::ID, E::OPTIONAL, &payload, false)
+    }
+
+    /// Writes the index of restart points, if enabled, and a checksum of the last block, if
+    /// there's anything written since the previous one, and returns the underlying writer.
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.write_tail()?;
+        self.writer.flush()?;
+        Ok(self.writer.inner)
+    }
+
+    /// Writes whatever [PrefixEncoder::finish] writes at the end of the stream. Calling it again
+    /// writes nothing.
+    pub(crate) fn write_tail(&mut self) -> std::io::Result<()> {
+        self.flush_block()?;
+        let index_offset = self.writer.pos;
+        let index = self.index.take();
+        if let Some(index) = &index {
+            for payload in encode_index(index) {
+                self.write_ext(EXT_INDEX, true, &payload, false)?;
+            }
+        }
+        if self.checksum_interval > 0 && self.writer.len > 0 {
+            self.write_checksum()?;
+        }
+        if index.is_some() {
+            self.write_builtin(&IndexTrailer { index_offset })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the underlying writer.
+    #[cfg(feature = "async")]
+    pub(crate) fn writer_mut(&mut self) -> &mut W {
+        &mut self.writer.inner
+    }
+
+    fn write_checksum(&mut self) -> std::io::Result<()> {
+        self.flush_block()?;
+        let checksum = BlockChecksum {
+            block: self.block,
+            crc: self.writer.crc,
+        };
+        self.write_builtin(&checksum)?;
+        self.writer.reset();
+        self.block += 1;
+        self.block_entries = 0;
+        Ok(())
+    }
+
+    /// Writes the pending entries as a compressed block.
+    fn flush_block(&mut self) -> std::io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let mut payload = Vec::new();
+        compress(&self.pending, &mut payload);
+        self.pending.clear();
+        self.write_ext(EXT_COMPRESSED, false, &payload, false)
+    }
+
+    /// Returns the output for an item of `len` bytes: the pending compressed block, if the item
+    /// may be compressed and fits there, or the stream.
+    fn output(&mut self, len: usize, compressible: bool) -> std::io::Result<&mut dyn Write> {
+        let compressible = compressible && len <= self.compression_block;
+        if !compressible || self.pending.len() + len > self.compression_block {
+            self.flush_block()?;
+        }
+        if compressible {
+            Ok(&mut self.pending)
+        } else {
+            Ok(&mut self.writer)
+        }
+    }
+
+    fn write_ext(
+        &mut self,
+        id: u16,
+        optional: bool,
+        payload: &[u8],
+        compressible: bool,
+    ) -> std::io::Result<()> {
+        if id as usize > MAX_PATH_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("extension id {id} exceeds {MAX_PATH_LEN}"),
+            ));
+        }
+        if payload.len() > u16::MAX as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("extension payload length {} exceeds 64KiB", payload.len()),
+            ));
+        }
+        let mut kind = id.to_be_bytes();
+        if optional {
+            kind[0] |= EXT_ENTRY;
+        }
+        let out = self.output(6 + payload.len(), compressible)?;
+        out.write_all(&[EXT_ENTRY, 0])?;
+        out.write_all(&(payload.len() as u16).to_be_bytes())?;
+        out.write_all(&kind)?;
+        out.write_all(payload)
+    }
+
     pub fn write_next(&mut self, key: &[u8], value: &[u8]) -> std::io::Result<()> {
-        debug_assert!(key.len() <= MAX_PATH_LEN);
-        debug_assert!(value.len() <= u16::MAX as usize);
+        if key.len() > MAX_PATH_LEN {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                Error::KeyTooLong(key.len()),
+            ));
+        }
+        if value.len() > u16::MAX as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("value length {} exceeds 64KiB limit", value.len()),
+            ));
+        }
 
-        let prefix_len = common_prefix(&self.last_key, &key);
+        let restart = self.restart_interval > 0 && self.restart_entries == 0;
+        let prefix_len = if restart {
+            // restart points must be reachable without decompressing preceding blocks
+            self.flush_block()?;
+            if let Some(index) = &mut self.index {
+                index.push(RestartPoint {
+                    key: key.to_vec(),
+                    offset: self.writer.pos,
+                });
+            }
+            self.writer.write_all(&RESTART_MARKER)?;
+            0
+        } else {
+            common_prefix(&self.last_key, key)
+        };
+        let diff = &key[prefix_len..];
+        // entries of restart points are written uncompressed, so that they can be recognized
+        // behind their markers
+        let out = self.output(6 + diff.len() + value.len(), !restart)?;
 
         // write entry header - length of key, of shared prefix between last key and current key
         // and finally length of value
-        self.writer.write_all(&(key.len() as u16).to_be_bytes())?;
-        self.writer.write_all(&(value.len() as u16).to_be_bytes())?;
-        self.writer.write_all(&(prefix_len as u16).to_be_bytes())?;
+        out.write_all(&(key.len() as u16).to_be_bytes())?;
+        out.write_all(&(value.len() as u16).to_be_bytes())?;
+        out.write_all(&(prefix_len as u16).to_be_bytes())?;
 
         // write key part that differs from the last key
-        let diff = &key[prefix_len..];
-        self.writer.write_all(diff)?;
+        out.write_all(diff)?;
+
+        // write value
+        out.write_all(value)?;
 
         // memorize the new last key
         self.last_key.drain(prefix_len..);
         self.last_key.extend_from_slice(diff);
 
-        // write value
-        self.writer.write_all(&value)?;
+        if self.restart_interval > 0 {
+            self.restart_entries = (self.restart_entries + 1) % self.restart_interval;
+        }
 
+        if self.checksum_interval > 0 {
+            self.block_entries += 1;
+            if self.block_entries == self.checksum_interval {
+                self.write_checksum()?;
+            }
+        }
         Ok(())
     }
+
+    /// Writes binary data read from `reader` as a bytes value stored under `key`. Returns the
+    /// number of bytes read.
+    ///
+    /// Data that fits into a single entry is written as a single bytes value. Otherwise, an
+    /// empty bytes value is written first, followed by continuation entries (`key[offset]..`)
+    /// carrying consecutive chunks of the data, so that it never has to be kept in memory as
+    /// a whole.
+    pub fn write_blob<B: Read>(&mut self, key: &[u8], mut reader: B) -> std::io::Result<u64> {
+        // one byte more than fits into a single entry tells if the data must be split
+        let mut buf = vec![0u8; MAX_CHUNK_LEN + 1];
+        buf[0] = TAG_BYTES;
+        let len = read_full(&mut reader, &mut buf[1..])?;
+        if len < MAX_CHUNK_LEN {
+            self.write_next(key, &buf[..=len])?;
+            return Ok(len as u64);
+        }
+
+        self.write_next(key, &[TAG_BYTES])?;
+        let mut path = PathBuf::new(key.to_vec());
+        // data already read is shifted by a tag byte, move it to the start of the buffer
+        buf.copy_within(1.., 0);
+        buf.truncate(MAX_CHUNK_LEN);
+        let mut len = MAX_CHUNK_LEN;
+        let mut offset = 0u64;
+        while len > 0 {
+            path.truncate(key.len());
+            path.push_index(offset)?;
+            path.push_continued()?;
+            self.write_next(path.as_bytes(), &buf[..len])?;
+            offset += len as u64;
+            len = read_full(&mut reader, &mut buf)?;
+        }
+        Ok(offset)
+    }
+}
+
+/// Reads from `reader` until `buf` is full or the reader is exhausted.
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// Returns the number of bytes following the entry header, as read by [PrefixDecoder].
+#[cfg(feature = "async")]
+pub(crate) fn body_len(header_buf: &[u8; 6]) -> usize {
+    let (key_len, value_len, prefix_len) = split_header(header_buf);
+    if header_buf[0] & EXT_ENTRY != 0 {
+        (key_len & MAX_PATH_LEN) + value_len
+    } else {
+        key_len.saturating_sub(prefix_len) + value_len
+    }
+}
+
+/// Splits entry header into key length, value length and prefix length fields.
+pub(crate) fn split_header(header_buf: &[u8; 6]) -> (usize, usize, usize) {
+    let key_len = u16::from_be_bytes([header_buf[0], header_buf[1]]) as usize;
+    let value_len = u16::from_be_bytes([header_buf[2], header_buf[3]]) as usize;
+    let prefix_len = u16::from_be_bytes([header_buf[4], header_buf[5]]) as usize;
+    (key_len, value_len, prefix_len)
 }
 
 fn common_prefix(xs: &[u8], ys: &[u8]) -> usize {
@@ -68,23 +419,268 @@ fn common_prefix_chunked<const N: usize>(xs: &[u8], ys: &[u8]) -> usize {
         .count()
 }
 
+/// Limits enforced by [PrefixDecoder] on the decoded stream, so that input coming from untrusted
+/// peers can't make it do an unbounded amount of work per entry or in total.
+///
+/// Default limits are the maximums allowed by the format, with no limit on number of entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecoderLimits {
+    /// Maximum length of an entry key in bytes.
+    pub max_key_len: usize,
+    /// Maximum length of an entry value in bytes.
+    pub max_value_len: usize,
+    /// Maximum number of entries in the stream, not counting the skipped extension entries.
+    pub max_entries: u64,
+    /// Maximum number of consecutive extension entries skipped while reading a single entry.
+    pub max_extension_skips: usize,
+}
+
+impl Default for DecoderLimits {
+    fn default() -> Self {
+        Self {
+            max_key_len: MAX_PATH_LEN,
+            max_value_len: u16::MAX as usize,
+            max_entries: u64::MAX,
+            max_extension_skips: usize::MAX,
+        }
+    }
+}
+
+/// Decoder of entries written by [PrefixEncoder].
+///
+/// Decoding never panics nor reads uninitialized memory, no matter what bytes it's given.
+/// Malformed streams are reported as [Error], while the amount of work and memory spent on
+/// any single entry is bounded by [DecoderLimits]. Paths are validated lazily, once their
+/// segments are iterated.
 pub struct PrefixDecoder<R> {
     last_key: Vec<u8>,
     last_value: Vec<u8>,
-    reader: R,
+    reader: Input<R>,
+    /// If set, the next call to `read_next` returns the last entry again.
+    replay: bool,
+    limits: DecoderLimits,
+    /// Number of entries read so far.
+    entries: u64,
+    /// Set until anything is read from the stream, as that's the only place for a header.
+    at_start: bool,
+    /// Header of the first entry, read by `read_stream_header` while looking for a stream
+    /// header, which is returned again by the next `read_entry_header`.
+    peeked: Option<[u8; 6]>,
+    header: Option<StreamHeader>,
+    /// Ids of extension kinds returned by `read_item`.
+    extensions: Vec<u16>,
+    /// Payload of the last read extension entry.
+    ext_payload: Vec<u8>,
+    /// Index of the current checksummed block.
+    block: u64,
+    /// Unset if parts of the current block have been read bypassing the checksum.
+    block_verifiable: bool,
+    /// Restart points of the stream, loaded by the first `seek_to`.
+    index: Option<Vec<RestartPoint>>,
+    /// Position of the last read entry within the stream.
+    entry_pos: u64,
+    /// Number of extension entries skipped since the last returned item.
+    skipped: usize,
+    /// Length of the prefix the last read key shares with the previous one.
+    prefix_len: usize,
+}
+
+/// Item read from the stream, with only its header and key consumed.
+enum RawItem {
+    Entry {
+        value_len: usize,
+    },
+    Extension {
+        id: u16,
+        optional: bool,
+        payload_len: usize,
+    },
 }
 
 impl<R: Read> PrefixDecoder<R> {
     pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, DecoderLimits::default())
+    }
+
+    pub fn with_limits(reader: R, limits: DecoderLimits) -> Self {
         Self {
             last_key: Vec::new(),
             last_value: Vec::new(),
-            reader,
+            reader: Input::new(Crc::new(reader, true)),
+            replay: false,
+            limits,
+            entries: 0,
+            at_start: true,
+            peeked: None,
+            header: None,
+            extensions: Vec::new(),
+            ext_payload: Vec::new(),
+            block: 0,
+            block_verifiable: true,
+            index: None,
+            entry_pos: 0,
+            skipped: 0,
+            prefix_len: 0,
+        }
+    }
+
+    /// Makes [PrefixDecoder::read_item] return extension entries of kind `E` instead of
+    /// skipping them. Mandatory entries of registered kinds are skipped by
+    /// [PrefixDecoder::read_next] instead of failing it.
+    pub fn register_extension<E: Extension>(&mut self) {
+        if !self.extensions.contains(&E::ID) {
+            self.extensions.push(E::ID);
+        }
+    }
+
+    /// Reads the stream header, which must be the very first entry of the stream. Fails with
+    /// [Error::MissingHeader] if the stream doesn't start with one, which means that it either
+    /// was written without a header or isn't a PEON stream at all.
+    ///
+    /// The header is optional: if there's none, nothing is consumed and the first entry can
+    /// still be read by [PrefixDecoder::read_next]. Calling this method is optional as well,
+    /// [PrefixDecoder::read_next] checks the header if there is one, see
+    /// [PrefixDecoder::header].
+    pub fn read_stream_header(&mut self) -> Result<StreamHeader, Error> {
+        if self.at_start
+            && self.peeked.is_none()
+            && let Some(header_buf) = self.read_entry_header()?
+        {
+            let (key_len, value_len, prefix_len) = split_header(&header_buf);
+            if header_buf[0] & EXT_ENTRY != 0 && (prefix_len & MAX_PATH_LEN) as u16 == EXT_HEADER {
+                self.at_start = false;
+                self.read_header_ext(key_len & MAX_PATH_LEN, value_len)?;
+            } else {
+                self.peeked = Some(header_buf);
+            }
         }
+        self.header.ok_or(Error::MissingHeader)
+    }
+
+    /// Returns the stream header, if the stream started with one.
+    pub fn header(&self) -> Option<StreamHeader> {
+        self.header
+    }
+
+    fn read_header_ext(&mut self, key_len: usize, value_len: usize) -> Result<(), Error> {
+        Self::skip(&mut self.reader, key_len)?;
+        let mut payload = vec![0u8; value_len];
+        self.reader.read_exact(&mut payload)?;
+        self.header = Some(StreamHeader::decode(&payload)?);
+        // checksummed blocks start right after the header
+        self.reader.stream.reset();
+        Ok(())
+    }
+
+    /// Verifies the checksum of a block against its CRC computed while reading it.
+    fn read_checksum_ext(
+        &mut self,
+        key_len: usize,
+        value_len: usize,
+        crc: u32,
+    ) -> Result<(), Error> {
+        Self::skip(&mut self.reader, key_len)?;
+        let mut payload = vec![0u8; value_len];
+        self.reader.read_exact(&mut payload)?;
+        let checksum = BlockChecksum::decode(&payload)?;
+        if !self.block_verifiable {
+            // after a seek, we don't know which block we're in
+            self.block = checksum.block;
+        } else if checksum.block != self.block || checksum.crc != crc {
+            return Err(Error::ChecksumMismatch { block: self.block });
+        }
+        self.block += 1;
+        self.block_verifiable = true;
+        self.reader.stream.reset();
+        Ok(())
+    }
+
+    /// Decompresses a block of entries, which are read from it before anything else.
+    fn read_compressed_ext(
+        &mut self,
+        key_len: usize,
+        value_len: usize,
+        pos: u64,
+    ) -> Result<(), Error> {
+        Self::skip(&mut self.reader, key_len)?;
+        self.ext_payload.resize(value_len, 0);
+        self.reader.read_exact(&mut self.ext_payload)?;
+        decompress(&self.ext_payload, &mut self.reader.block)?;
+        self.reader.block_pos = 0;
+        self.reader.block_start = pos;
+        Ok(())
+    }
+
+    /// Makes the next call to [PrefixDecoder::read_next] return the last read entry again.
+    ///
+    /// Useful for readers which need to look at the entry following a value in order to know
+    /// where the value ends.
+    pub fn unread(&mut self) {
+        self.replay = true;
+    }
+
+    /// Returns a reader over a bytes value of the last read entry, including all of its
+    /// continuation entries written by [PrefixEncoder::write_blob].
+    pub fn blob_reader(&mut self) -> std::io::Result<BlobReader<'_, R>> {
+        BlobReader::new(self)
+    }
+
+    /// Reassembles a bytes value of the last read entry, including all of its continuation
+    /// entries written by [PrefixEncoder::write_blob].
+    pub fn read_blob(&mut self) -> std::io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.blob_reader()?.read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub(crate) fn last_key(&self) -> &[u8] {
+        &self.last_key
+    }
+
+    pub(crate) fn last_value(&self) -> &[u8] {
+        &self.last_value
+    }
+
+    /// Returns the position of the header of the last read entry, relative to the start of
+    /// the stream.
+    ///
+    /// Decoders working on a byte range of a stream, positioned by
+    /// [PrefixDecoder::seek_to_restart], use it to find out where the range ends: the first
+    /// entry at or past the end of the range belongs to the next one.
+    pub fn entry_position(&self) -> u64 {
+        self.entry_pos
+    }
+
+    /// Replaces the last read entry with an entry under `key` with an empty value, so that
+    /// the next entry is decoded relative to `key`. Entry held for replay, if any, is dropped.
+    pub(crate) fn reset_last(&mut self, key: &[u8]) {
+        self.last_key.clear();
+        self.last_key.extend_from_slice(key);
+        self.last_value.clear();
+        self.replay = false;
+    }
+
+    /// Returns whether the last read entry comes from a compressed block.
+    pub(crate) fn in_block(&self) -> bool {
+        self.reader.in_block
+    }
+
+    /// Returns the underlying reader for appending data to it. Use [PrefixDecoder::reader_mut]
+    /// for reading.
+    #[cfg(feature = "async")]
+    pub(crate) fn source_mut(&mut self) -> &mut R {
+        &mut self.reader.stream.inner
+    }
+
+    /// Returns the underlying reader. Data read from it directly bypasses checksums, so the
+    /// current block won't be verified.
+    pub(crate) fn reader_mut(&mut self) -> &mut R {
+        self.block_verifiable = false;
+        &mut self.reader.stream.inner
     }
 
     #[inline(never)]
-    fn skip(reader: &mut R, len: usize) -> std::io::Result<()> {
+    fn skip(reader: &mut impl Read, len: usize) -> Result<(), Error> {
         let mut remaining = len;
         let mut buf = [0u8; 1024]; // buffer to read and discard
         while remaining > 0 {
@@ -95,49 +691,278 @@ impl<R: Read> PrefixDecoder<R> {
         Ok(())
     }
 
-    pub fn read_next(&mut self) -> std::io::Result<Option<(Path, &[u8])>> {
+    /// Reads the entry header, returning `None` if the stream ends right before it.
+    fn read_entry_header(&mut self) -> Result<Option<[u8; 6]>, Error> {
+        if let Some(header_buf) = self.peeked.take() {
+            return Ok(Some(header_buf));
+        }
         let mut header_buf = [0u8; 6];
-        match self.reader.read_exact(&mut header_buf) {
-            Ok(()) => {}
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+        let mut filled = 0;
+        while filled < header_buf.len() {
+            match self.reader.read(&mut header_buf[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(Error::Truncated),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+        Ok(Some(header_buf))
+    }
+
+    /// Reads the header and the key of the next entry or the header of the next extension entry,
+    /// leaving value or payload in the reader.
+    fn read_raw(&mut self) -> Result<Option<RawItem>, Error> {
+        loop {
+            // checksum of the current block doesn't include the checksum entry
+            let (crc, block_len) = (self.reader.stream.crc, self.reader.stream.len);
+            self.reader.next_item();
+            let in_block = self.reader.in_block;
+            let pos = if in_block {
+                self.reader.block_start
+            } else if self.peeked.is_some() {
+                // header of the first entry has been read already
+                self.reader.stream.pos - 6
+            } else {
+                self.reader.stream.pos
+            };
+            let Some(header_buf) = self.read_entry_header()? else {
                 // No more entries to read
+                if self.reader.stream.len > 0
+                    && self
+                        .header
+                        .is_some_and(|h| h.has_flag(StreamHeader::FLAG_CHECKSUMS))
+                {
+                    return Err(Error::MissingChecksum { block: self.block });
+                }
                 return Ok(None);
+            };
+            let at_start = std::mem::replace(&mut self.at_start, false);
+            let (key_len, value_len, prefix_len) = split_header(&header_buf);
+
+            if header_buf[0] & EXT_ENTRY != 0 {
+                let id = (prefix_len & MAX_PATH_LEN) as u16;
+                if id == EXT_HEADER && at_start {
+                    self.read_header_ext(key_len & MAX_PATH_LEN, value_len)?;
+                    continue;
+                }
+                if id == EXT_CHECKSUM || id == EXT_INDEX_TRAILER || id == EXT_COMPRESSED {
+                    if in_block {
+                        // framing entries can't be nested in compressed blocks
+                        return Err(Error::InvalidExtension(id));
+                    }
+                    self.count_skip()?;
+                    if id == EXT_CHECKSUM {
+                        self.read_checksum_ext(key_len & MAX_PATH_LEN, value_len, crc)?;
+                    } else if id == EXT_COMPRESSED {
+                        self.read_compressed_ext(key_len & MAX_PATH_LEN, value_len, pos)?;
+                    } else {
+                        // trailer follows the last checksum, it's not a part of any block
+                        Self::skip(&mut self.reader, (key_len & MAX_PATH_LEN) + value_len)?;
+                        self.reader.stream.crc = crc;
+                        self.reader.stream.len = block_len;
+                    }
+                    continue;
+                }
+                // key part of extension entries is reserved for future use
+                Self::skip(&mut self.reader, key_len & MAX_PATH_LEN)?;
+                return Ok(Some(RawItem::Extension {
+                    id,
+                    optional: header_buf[4] & EXT_ENTRY != 0,
+                    payload_len: value_len,
+                }));
             }
-            Err(e) => return Err(e),
+
+            if key_len > self.limits.max_key_len {
+                return Err(Error::KeyTooLong(key_len));
+            }
+            if value_len > self.limits.max_value_len {
+                return Err(Error::ValueTooLong(value_len));
+            }
+            if self.entries == self.limits.max_entries {
+                return Err(Error::TooManyEntries(self.entries));
+            }
+            if prefix_len > key_len || prefix_len > self.last_key.len() {
+                return Err(Error::PrefixOutOfRange {
+                    prefix_len,
+                    last_len: self.last_key.len(),
+                    key_len,
+                });
+            }
+
+            // make sure key buffer is large enough and read it starting from the prefix offset
+            self.last_key.resize(key_len, 0);
+            self.reader.read_exact(&mut self.last_key[prefix_len..])?;
+            self.entries += 1;
+            self.entry_pos = pos;
+            self.skipped = 0;
+            self.prefix_len = prefix_len;
+            return Ok(Some(RawItem::Entry { value_len }));
         }
+    }
 
-        let key_len = u16::from_be_bytes([header_buf[0], header_buf[1]]) as usize;
-        let value_len = u16::from_be_bytes([header_buf[2], header_buf[3]]) as usize;
-        let prefix_len = u16::from_be_bytes([header_buf[4], header_buf[5]]) as usize;
+    /// Skips the payload of an extension entry which isn't returned to the caller, failing if
+    /// the entry is mandatory and its kind is unknown.
+    fn skip_ext(&mut self, id: u16, optional: bool, payload_len: usize) -> Result<(), Error> {
+        if !optional && !self.extensions.contains(&id) {
+            return Err(Error::UnsupportedExtension(id));
+        }
+        self.count_skip()?;
+        Self::skip(&mut self.reader, payload_len)
+    }
 
-        if header_buf[0] & EXT_ENTRY != 0 {
-            // this is an extension entry, which we do not support yet
-            if header_buf[4] & EXT_ENTRY != 0 {
-                // this entry is optional, we can skip it
-                let skip_len = (key_len & MAX_PATH_LEN) + value_len;
-                Self::skip(&mut self.reader, skip_len)?;
+    /// Counts an extension entry skipped on the way to the next item against the limit.
+    fn count_skip(&mut self) -> Result<(), Error> {
+        if self.skipped == self.limits.max_extension_skips {
+            return Err(Error::TooManyExtensions(self.skipped));
+        }
+        self.skipped += 1;
+        Ok(())
+    }
 
-                // read next entry
-                return self.read_next();
-            } else {
-                // this entry is mandatory, but we do not support it yet
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Unsupported,
-                    "Received non-optional entry of unsupported type",
-                ));
+    /// Reads the header and the key of the next entry, leaving its value in the reader.
+    /// Returns the length of the value, or `None` if there are no more entries.
+    pub(crate) fn read_key(&mut self) -> Result<Option<usize>, Error> {
+        loop {
+            match self.read_raw()? {
+                None => return Ok(None),
+                Some(RawItem::Entry { value_len }) => return Ok(Some(value_len)),
+                Some(RawItem::Extension {
+                    id,
+                    optional,
+                    payload_len,
+                }) => self.skip_ext(id, optional, payload_len)?,
             }
         }
+    }
+
+    /// Reads the next item of the stream, which is either an entry or an extension entry of
+    /// a kind registered with [PrefixDecoder::register_extension].
+    pub fn read_item(&mut self) -> Result<Option<DecodedItem<'_>>, Error> {
+        if self.replay {
+            self.replay = false;
+            let path = Path::from_slice(&self.last_key);
+            return Ok(Some(DecodedItem::Entry(path, self.last_value.as_slice())));
+        }
+
+        loop {
+            match self.read_raw()? {
+                None => return Ok(None),
+                Some(RawItem::Entry { value_len }) => {
+                    self.read_value(value_len)?;
+                    let path = Path::from_slice(&self.last_key);
+                    return Ok(Some(DecodedItem::Entry(path, self.last_value.as_slice())));
+                }
+                Some(RawItem::Extension {
+                    id,
+                    optional,
+                    payload_len,
+                }) if self.extensions.contains(&id) => {
+                    if payload_len > self.limits.max_value_len {
+                        return Err(Error::ValueTooLong(payload_len));
+                    }
+                    self.ext_payload.resize(payload_len, 0);
+                    self.reader.read_exact(&mut self.ext_payload)?;
+                    self.skipped = 0;
+                    return Ok(Some(DecodedItem::Extension(ExtensionEntry {
+                        id,
+                        optional,
+                        payload: &self.ext_payload,
+                    })));
+                }
+                Some(RawItem::Extension {
+                    id,
+                    optional,
+                    payload_len,
+                }) => self.skip_ext(id, optional, payload_len)?,
+            }
+        }
+    }
+
+    /// Skips entries at or under the path made of the first `len` bytes of the last read key,
+    /// see [PrefixDecoder::skip_prefixed].
+    pub(crate) fn skip_under(&mut self, len: usize) -> Result<Option<usize>, Error> {
+        let prefix = self.last_key[..len].to_vec();
+        self.skip_prefixed(&prefix)
+    }
 
-        // make sure key buffer is large enough and read it starting from the prefix offset
-        self.last_key.resize(key_len, 0);
-        self.reader.read_exact(&mut self.last_key[prefix_len..])?;
+    /// Skips entries with keys at or under the encoded path `prefix`, without reading their
+    /// values. Returns the value length of the first entry following them, with its key read,
+    /// or `None` if there are no more entries.
+    ///
+    /// Keys of skipped entries are still reconstructed, as the key of the entry following them
+    /// is encoded relative to the last of them.
+    fn skip_prefixed(&mut self, prefix: &[u8]) -> Result<Option<usize>, Error> {
+        // keys sharing the whole prefix with a key under it are under it as well, only restart
+        // points, which don't share anything, need to be compared
+        let mut under = self.last_key.starts_with(prefix);
+        loop {
+            let Some(value_len) = self.read_key()? else {
+                return Ok(None);
+            };
+            under = (under && self.prefix_len >= prefix.len()) || self.last_key.starts_with(prefix);
+            if !under || !is_segment_boundary(&self.last_key, prefix.len()) {
+                return Ok(Some(value_len));
+            }
+            Self::skip(&mut self.reader, value_len)?;
+        }
+    }
 
-        // read value
-        if self.last_value.len() < value_len {
-            self.last_value.reserve(value_len - self.last_value.len());
+    /// Skips all following entries at or under `path` without reading their values, so that
+    /// the next read returns the first entry outside of it. Returns the number of skipped
+    /// entries.
+    ///
+    /// Whether an entry is under `path` is mostly decided by the length of the prefix its key
+    /// shares with the previous key, so skipped keys aren't parsed, and they're compared with
+    /// `path` only when that prefix is shorter than `path`, e.g. at restart points. They're still
+    /// reconstructed though, as the key of the entry following them is encoded relative to the
+    /// last of them: skipping copies the differing part of every key, but no value bytes.
+    pub fn skip_while_prefix(&mut self, path: &Path) -> Result<u64, Error> {
+        let prefix = path.as_bytes();
+        let mut skipped = 0;
+        if self.replay {
+            if !self.last_key.starts_with(prefix)
+                || !is_segment_boundary(&self.last_key, prefix.len())
+            {
+                return Ok(0);
+            }
+            self.replay = false;
+            skipped += 1;
         }
-        unsafe { self.last_value.set_len(value_len) };
+        let start = self.entries;
+        match self.skip_prefixed(prefix)? {
+            Some(value_len) => {
+                self.read_value(value_len)?;
+                self.unread();
+                Ok(skipped + self.entries - start - 1)
+            }
+            None => Ok(skipped + self.entries - start),
+        }
+    }
+
+    /// Returns a cursor over the entries of the stream, starting before the next entry.
+    pub fn cursor(&mut self) -> DecoderCursor<'_, R> {
+        DecoderCursor::new(self)
+    }
+
+    pub(crate) fn read_value(&mut self, value_len: usize) -> Result<(), Error> {
+        self.last_value.resize(value_len, 0);
         self.reader.read_exact(&mut self.last_value)?;
+        Ok(())
+    }
+
+    pub fn read_next(&mut self) -> Result<Option<(Path<'_>, &[u8])>, Error> {
+        if self.replay {
+            self.replay = false;
+            let path = Path::from_slice(&self.last_key);
+            return Ok(Some((path, self.last_value.as_slice())));
+        }
+
+        let Some(value_len) = self.read_key()? else {
+            return Ok(None);
+        };
+
+        self.read_value(value_len)?;
 
         let path = Path::from_slice(&self.last_key);
         let value = self.last_value.as_slice();
@@ -145,11 +970,270 @@ impl<R: Read> PrefixDecoder<R> {
     }
 }
 
+impl<R: Read> PrefixDecoder<R> {
+    /// Reads the next entry matching `query`, including its filter predicates. Once an entry
+    /// rules out matches of anything under some prefix of its path, e.g. `$.items[0].snippet`
+    /// for `$.items[*].id`, the following entries under that prefix are skipped without reading
+    /// their values.
+    pub fn read_next_matching(
+        &mut self,
+        query: &JsonPath,
+    ) -> Result<Option<(Path<'_>, &[u8])>, Error> {
+        if self.replay {
+            self.replay = false;
+            if query.is_match_entry(&Path::from_slice(&self.last_key), &self.last_value) {
+                let path = Path::from_slice(&self.last_key);
+                return Ok(Some((path, self.last_value.as_slice())));
+            }
+        }
+
+        let mut next = self.read_key()?;
+        while let Some(value_len) = next {
+            let path = Path::from_slice(&self.last_key);
+            if query.is_match(&path) {
+                self.read_value(value_len)?;
+                if query.is_match_entry(&Path::from_slice(&self.last_key), &self.last_value) {
+                    let path = Path::from_slice(&self.last_key);
+                    return Ok(Some((path, self.last_value.as_slice())));
+                }
+                next = self.read_key()?;
+                continue;
+            }
+            let mismatch = query.mismatch_len(&path);
+            Self::skip(&mut self.reader, value_len)?;
+            next = match mismatch {
+                Some(len) => self.skip_under(len)?,
+                None => self.read_key()?,
+            };
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read + Seek> PrefixDecoder<R> {
+    /// Returns a seekable reader over a string or bytes value of the last read entry,
+    /// including all of its continuation entries.
+    ///
+    /// The last read entry can be either a string or bytes value, or the first continuation
+    /// entry (`path[0]..`) of a string split into chunks.
+    pub fn chunked_value_reader(&mut self) -> std::io::Result<ChunkedValueReader<'_, R>> {
+        ChunkedValueReader::new(self)
+    }
+
+    /// Positions the decoder at the first entry with path not less than `path`, so that it's
+    /// returned by the next [PrefixDecoder::read_next].
+    ///
+    /// The stream must be written by [PrefixEncoder::with_index] and start at the beginning of
+    /// the reader. Index is loaded from the end of the stream on the first call, and the closest
+    /// restart point preceding `path` is found by a binary search, so at most a restart interval
+    /// of entries is decoded on every call. Fails with [Error::MissingIndex] if there's no index.
+    pub fn seek_to(&mut self, path: &Path) -> Result<(), Error> {
+        if self.index.is_none() {
+            let index = self.read_index()?;
+            // restart points can only be searched by path in streams sorted by it
+            if !index.is_sorted_by(|a, b| a.key <= b.key) {
+                return Err(Error::InvalidIndex);
+            }
+            self.index = Some(index);
+        }
+        let index = self.index.as_deref().unwrap_or_default();
+        let i = index.partition_point(|point| point.key.as_slice() <= path.as_bytes());
+        let Some(point) = index.get(i.saturating_sub(1)) else {
+            // stream without entries
+            let end = self.reader.stream.inner.seek(SeekFrom::End(0))?;
+            return self.restart_at(end);
+        };
+        self.restart_at(point.offset)?;
+        while let Some((key, _)) = self.read_next()? {
+            if key.as_bytes() >= path.as_bytes() {
+                self.unread();
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Positions the decoder at the first restart point written by
+    /// [PrefixEncoder::with_restart_interval] at or after `offset`, returning its position
+    /// within the stream, or `None` if there are no restart points left.
+    ///
+    /// This lets a large stream split into byte ranges be decoded in parallel, each range by
+    /// its own decoder. A range starts at the first restart point at or after its start offset,
+    /// and ends right before the first entry positioned at or after the restart point following
+    /// its end offset, see [PrefixDecoder::entry_position]. The stream must start at the
+    /// beginning of the reader.
+    pub fn seek_to_restart(&mut self, offset: u64) -> Result<Option<u64>, Error> {
+        let mut pos = self.reader.stream.inner.seek(SeekFrom::Start(offset))?;
+        let mut buf = vec![0u8; 64 * 1024];
+        let mut filled = 0;
+        loop {
+            filled += read_full(&mut self.reader.stream.inner, &mut buf[filled..])?;
+            let mut from = 0;
+            while let Some(i) = buf[from..filled]
+                .windows(RESTART_MARKER.len())
+                .position(|window| window == RESTART_MARKER)
+            {
+                let restart = pos + (from + i) as u64;
+                if self.is_restart(restart)? {
+                    self.restart_at(restart)?;
+                    return Ok(Some(restart));
+                }
+                from += i + 1;
+            }
+            if filled < buf.len() {
+                // end of the stream
+                self.restart_at(pos + filled as u64)?;
+                return Ok(None);
+            }
+            // keep the tail, which may contain the beginning of a marker
+            let keep = RESTART_MARKER.len() - 1;
+            buf.copy_within(filled - keep.., 0);
+            pos += (filled - keep) as u64;
+            filled = keep;
+            self.reader
+                .stream
+                .inner
+                .seek(SeekFrom::Start(pos + filled as u64))?;
+        }
+    }
+
+    /// Returns the position of the last restart point before `end`, searching the stream
+    /// backwards for restart markers, or `None` if there is none.
+    pub(crate) fn restart_before(&mut self, end: u64) -> Result<Option<u64>, Error> {
+        const WINDOW: usize = 64 * 1024;
+        // windows overlap, so that markers crossing their ends are found
+        let mut buf = vec![0u8; WINDOW + RESTART_MARKER.len() - 1];
+        let mut window_end = end;
+        while window_end > 0 {
+            let start = window_end.saturating_sub(WINDOW as u64);
+            let reader = &mut self.reader.stream.inner;
+            reader.seek(SeekFrom::Start(start))?;
+            let len = (window_end - start) as usize + RESTART_MARKER.len() - 1;
+            let mut filled = read_full(reader, &mut buf[..len])?;
+            while let Some(i) = buf[..filled]
+                .windows(RESTART_MARKER.len())
+                .rposition(|window| window == RESTART_MARKER)
+            {
+                let restart = start + i as u64;
+                if self.is_restart(restart)? {
+                    return Ok(Some(restart));
+                }
+                filled = i + RESTART_MARKER.len() - 1;
+            }
+            window_end = start;
+        }
+        Ok(None)
+    }
+
+    /// Tells if the restart marker found at `pos` belongs to a restart point, rather than being
+    /// a part of some binary value. Marker of a restart point is followed by an entry with
+    /// a full key, which is in turn followed by the end of the stream or by another entry that
+    /// continues from it.
+    fn is_restart(&mut self, pos: u64) -> Result<bool, Error> {
+        let reader = &mut self.reader.stream.inner;
+        reader.seek(SeekFrom::Start(pos + RESTART_MARKER.len() as u64))?;
+        let mut header_buf = [0u8; 6];
+        if read_full(reader, &mut header_buf)? < 6 {
+            return Ok(false);
+        }
+        let (key_len, value_len, prefix_len) = split_header(&header_buf);
+        if header_buf[0] & EXT_ENTRY != 0 || prefix_len != 0 {
+            return Ok(false);
+        }
+        let mut key = vec![0u8; key_len];
+        if read_full(reader, &mut key)? < key_len
+            || key.is_empty()
+            || Path::from_slice(&key)
+                .iter()
+                .any(|segment| segment.is_err())
+        {
+            return Ok(false);
+        }
+        reader.seek(SeekFrom::Current(value_len as i64))?;
+        match read_full(reader, &mut header_buf)? {
+            0 => Ok(true),
+            6 if header_buf[0] & EXT_ENTRY != 0 => Ok(header_buf[1] == 0),
+            6 => {
+                let (next_key_len, _, next_prefix_len) = split_header(&header_buf);
+                Ok(next_prefix_len <= key_len && next_prefix_len <= next_key_len)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Returns positions of the restart points listed in the index at the end of the stream.
+    pub(crate) fn restart_points(&mut self) -> Result<Vec<u64>, Error> {
+        Ok(self.read_index()?.into_iter().map(|p| p.offset).collect())
+    }
+
+    /// Moves the underlying reader to `pos`, bypassing checksums.
+    pub(crate) fn seek_reader(&mut self, pos: u64) -> std::io::Result<()> {
+        self.peeked = None;
+        self.block_verifiable = false;
+        self.reader.clear_block();
+        self.reader.stream.pos = self.reader.stream.inner.seek(SeekFrom::Start(pos))?;
+        Ok(())
+    }
+
+    /// Resumes decoding from a restart point at `pos`, which doesn't depend on entries before it.
+    pub(crate) fn restart_at(&mut self, pos: u64) -> Result<(), Error> {
+        self.seek_reader(pos)?;
+        self.reader.stream.reset();
+        self.reset_last(&[]);
+        self.at_start = false;
+        Ok(())
+    }
+
+    /// Reads restart points from the index at the end of the stream.
+    fn read_index(&mut self) -> Result<Vec<RestartPoint>, Error> {
+        let reader = &mut self.reader.stream.inner;
+        let end = reader.seek(SeekFrom::End(0))?;
+        let Some(trailer_start) = end.checked_sub(TRAILER_LEN as u64) else {
+            return Err(Error::MissingIndex);
+        };
+        reader.seek(SeekFrom::Start(trailer_start))?;
+        let mut tail = [0u8; TRAILER_LEN];
+        reader.read_exact(&mut tail)?;
+        let trailer = IndexTrailer::from_tail(&tail).ok_or(Error::MissingIndex)?;
+        if trailer.index_offset > trailer_start {
+            return Err(Error::InvalidIndex);
+        }
+
+        let mut pos = reader.seek(SeekFrom::Start(trailer.index_offset))?;
+        let mut index = Vec::new();
+        let mut payload = Vec::new();
+        // index entries are followed by an optional checksum and the trailer
+        while pos < trailer_start {
+            let mut header_buf = [0u8; 6];
+            reader.read_exact(&mut header_buf)?;
+            let (key_len, value_len, prefix_len) = split_header(&header_buf);
+            if header_buf[0] & EXT_ENTRY == 0 {
+                return Err(Error::InvalidIndex);
+            }
+            Self::skip(reader, key_len & MAX_PATH_LEN)?;
+            payload.resize(value_len, 0);
+            reader.read_exact(&mut payload)?;
+            match (prefix_len & MAX_PATH_LEN) as u16 {
+                EXT_INDEX => decode_index(&payload, &mut index)?,
+                EXT_CHECKSUM => {}
+                _ => return Err(Error::InvalidIndex),
+            }
+            pos += (6 + (key_len & MAX_PATH_LEN) + value_len) as u64;
+        }
+        if pos != trailer_start {
+            return Err(Error::InvalidIndex);
+        }
+        Ok(index)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::path::Encode;
     use crate::{PathBuf, PathSegment};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
     use std::collections::BTreeMap;
     use std::io::Cursor;
 
@@ -266,10 +1350,190 @@ mod test {
         assert_eq!(value, b"b");
 
         let res = decoder.read_next().unwrap_err();
-        assert_eq!(
-            res.kind(),
-            std::io::ErrorKind::Unsupported,
-            "Expected Unsupported error for unskippable entry"
+        assert!(
+            matches!(res, Error::UnsupportedExtension(0)),
+            "Expected UnsupportedExtension error for unskippable entry, got {res:?}"
         );
     }
+
+    #[test]
+    fn test_prefix_decoder_malformed() {
+        let a = PathBuf::from_iter([PathSegment::Key("a")]);
+        let mut buf = Vec::new();
+        PrefixEncoder::new(&mut buf)
+            .write_next(a.as_ref(), b"value")
+            .unwrap();
+
+        // stream cut in the middle of the header, key and value
+        for len in [3, 7, buf.len() - 1] {
+            let mut decoder = PrefixDecoder::new(&buf[..len]);
+            let res = decoder.read_next().unwrap_err();
+            assert!(matches!(res, Error::Truncated), "{len}: {res:?}");
+        }
+
+        // first entry cannot share a prefix with anything
+        let mut corrupted = buf.clone();
+        corrupted[5] = 1;
+        let mut decoder = PrefixDecoder::new(corrupted.as_slice());
+        let res = decoder.read_next().unwrap_err();
+        assert!(matches!(
+            res,
+            Error::PrefixOutOfRange {
+                prefix_len: 1,
+                last_len: 0,
+                key_len: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn test_prefix_encoder_key_too_long() {
+        let key = vec![b'a'; MAX_PATH_LEN + 1];
+        let mut encoder = PrefixEncoder::new(Vec::new());
+        let res = encoder.write_next(&key, b"").unwrap_err();
+        assert_eq!(res.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(encoder.write_next(&key[..MAX_PATH_LEN], b"").is_ok());
+    }
+
+    /// Optional extension entry with a single byte of payload.
+    const OPTIONAL_EXT: [u8; 7] = [EXT_ENTRY, 0, 0, 1, EXT_ENTRY, 0, 42];
+
+    #[test]
+    fn test_prefix_decoder_long_extension_run() {
+        let a = PathBuf::from_iter([PathSegment::Key("a")]);
+        let mut buf = OPTIONAL_EXT.repeat(100_000);
+        PrefixEncoder::new(&mut buf)
+            .write_next(a.as_ref(), b"a")
+            .unwrap();
+
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.as_path_buf(), a);
+        assert_eq!(value, b"a");
+
+        let limits = DecoderLimits {
+            max_extension_skips: 1000,
+            ..DecoderLimits::default()
+        };
+        let mut decoder = PrefixDecoder::with_limits(buf.as_slice(), limits);
+        let res = decoder.read_next().unwrap_err();
+        assert!(matches!(res, Error::TooManyExtensions(1000)), "{res:?}");
+    }
+
+    #[test]
+    fn test_prefix_decoder_limits() {
+        let short = PathBuf::from_iter([PathSegment::Key("a")]);
+        let long = PathBuf::from_iter([PathSegment::Key("abcdefgh")]);
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        encoder.write_next(short.as_ref(), b"a").unwrap();
+        encoder.write_next(long.as_ref(), b"b").unwrap();
+        encoder.write_next(short.as_ref(), &[0; 100]).unwrap();
+
+        let read_all = |limits: DecoderLimits| {
+            let mut decoder = PrefixDecoder::with_limits(buf.as_slice(), limits);
+            while decoder.read_next()?.is_some() {}
+            Ok::<_, Error>(())
+        };
+        let defaults = DecoderLimits::default();
+        read_all(defaults).unwrap();
+        let res = read_all(DecoderLimits {
+            max_key_len: 8,
+            ..defaults
+        });
+        assert!(matches!(res, Err(Error::KeyTooLong(9))), "{res:?}");
+        let res = read_all(DecoderLimits {
+            max_value_len: 99,
+            ..defaults
+        });
+        assert!(matches!(res, Err(Error::ValueTooLong(100))), "{res:?}");
+        let res = read_all(DecoderLimits {
+            max_entries: 2,
+            ..defaults
+        });
+        assert!(matches!(res, Err(Error::TooManyEntries(2))), "{res:?}");
+    }
+
+    #[test]
+    fn test_prefix_decoder_skip_while_prefix() {
+        let user = |i: u64, field: &str| {
+            PathBuf::from_iter([PathSegment::Key("users"), i.into(), field.into()])
+        };
+        let users = |i: u64| PathBuf::from_iter([PathSegment::Key("users"), i.into()]);
+        for restart_interval in [1, 2, 1000] {
+            let mut buf = Vec::new();
+            let mut encoder = PrefixEncoder::new(&mut buf).with_restart_interval(restart_interval);
+            for i in 0..20u64 {
+                for field in ["age", "name", "names"] {
+                    encoder
+                        .write_next(user(i, field).as_ref(), field.as_bytes())
+                        .unwrap();
+                }
+            }
+            encoder.finish().unwrap();
+
+            let mut decoder = PrefixDecoder::new(buf.as_slice());
+            // next entry isn't under the path
+            assert_eq!(decoder.skip_while_prefix(&users(1).as_path()).unwrap(), 0);
+            for _ in 0..3 {
+                decoder.read_next().unwrap().unwrap();
+            }
+            assert_eq!(decoder.skip_while_prefix(&users(1).as_path()).unwrap(), 3);
+            let (path, _) = decoder.read_next().unwrap().unwrap();
+            assert_eq!(path.as_path_buf(), user(2, "age"));
+
+            // `name` is a byte prefix of `names`, but not its path prefix
+            let name = user(2, "name");
+            assert_eq!(decoder.skip_while_prefix(&name.as_path()).unwrap(), 1);
+            let (path, _) = decoder.read_next().unwrap().unwrap();
+            assert_eq!(path.as_path_buf(), user(2, "names"));
+
+            // entry given back to the decoder is skipped as well
+            decoder.read_next().unwrap().unwrap();
+            decoder.unread();
+            assert_eq!(decoder.skip_while_prefix(&users(3).as_path()).unwrap(), 3);
+            let root = PathBuf::new(Vec::new());
+            assert_eq!(decoder.skip_while_prefix(&root.as_path()).unwrap(), 48);
+            assert!(decoder.read_next().unwrap().is_none());
+        }
+    }
+
+    fn decode_all(buf: &[u8]) -> Result<usize, Error> {
+        let mut decoder = PrefixDecoder::new(buf);
+        let mut count = 0;
+        while let Some((path, _)) = decoder.read_next()? {
+            for segment in path.iter() {
+                segment?;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    proptest! {
+        #[test]
+        fn prefix_decoder_arbitrary_bytes(buf in vec(any::<u8>(), 0..512)) {
+            let _ = decode_all(&buf);
+        }
+
+        #[test]
+        fn prefix_decoder_corrupted_stream(
+            flips in vec((any::<usize>(), any::<u8>()), 1..8),
+            cut: usize,
+        ) {
+            let mut buf = Vec::new();
+            let mut encoder = PrefixEncoder::new(&mut buf);
+            for i in 0..20u64 {
+                let path = PathBuf::from_iter([PathSegment::Key("users"), i.into(), "name".into()]);
+                encoder.write_next(path.as_ref(), b"value").unwrap();
+            }
+            buf.extend_from_slice(&OPTIONAL_EXT);
+            for (pos, byte) in flips {
+                let len = buf.len();
+                buf[pos % len] ^= byte;
+            }
+            buf.truncate(cut % (buf.len() + 1));
+            let _ = decode_all(&buf);
+        }
+    }
 }
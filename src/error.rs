@@ -0,0 +1,77 @@
+use crate::json::DecodeError;
+use crate::path::PathError;
+
+/// Error returned when reading a PEON stream.
+///
+/// Malformed input is always reported as one of these errors, whether it comes from
+/// the entry framing, paths or values.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to read PEON stream: {0}")]
+    Io(#[source] std::io::Error),
+    #[error("PEON stream ends in the middle of an entry")]
+    Truncated,
+    #[error("prefix length {prefix_len} out of range (previous key: {last_len}, key: {key_len})")]
+    PrefixOutOfRange {
+        prefix_len: usize,
+        last_len: usize,
+        key_len: usize,
+    },
+    #[error("key length {0} exceeds the limit")]
+    KeyTooLong(usize),
+    #[error("value length {0} exceeds the limit")]
+    ValueTooLong(usize),
+    #[error("number of entries exceeds the limit of {0}")]
+    TooManyEntries(u64),
+    #[error("number of consecutive extension entries exceeds the limit of {0}")]
+    TooManyExtensions(usize),
+    #[error("stream doesn't start with a PEON header")]
+    MissingHeader,
+    #[error("malformed PEON stream header")]
+    InvalidHeader,
+    #[error("unsupported PEON format version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("unsupported PEON stream flags: {0:#06x}")]
+    UnsupportedFlags(u16),
+    #[error("checksum mismatch in block {block}")]
+    ChecksumMismatch { block: u64 },
+    #[error("block {block} at the end of the stream has no checksum")]
+    MissingChecksum { block: u64 },
+    #[error("stream has no index of restart points")]
+    MissingIndex,
+    #[error("malformed index of restart points")]
+    InvalidIndex,
+    #[error("unsupported compression codec: {0}")]
+    UnsupportedCompression(u8),
+    #[error("malformed payload of extension entry: {0}")]
+    InvalidExtension(u16),
+    #[error("unsupported mandatory extension entry: {0}")]
+    UnsupportedExtension(u16),
+    #[error("invalid entry path: {0}")]
+    InvalidPath(#[from] PathError),
+    #[error("invalid entry value: {0}")]
+    InvalidValue(#[from] DecodeError),
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            Error::Truncated
+        } else {
+            Error::Io(e)
+        }
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::Truncated => std::io::Error::new(std::io::ErrorKind::UnexpectedEof, e),
+            Error::UnsupportedExtension(_) | Error::UnsupportedCompression(_) => {
+                std::io::Error::new(std::io::ErrorKind::Unsupported, e)
+            }
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{Error, Path};
+
+/// Smallest id available for user-defined extensions, smaller ones are reserved for extensions
+/// defined by PEON itself.
+pub const MIN_USER_ID: u16 = 64;
+
+/// Kind of extension entries, which carry metadata (timestamps, authorship, schema ids etc.)
+/// inside a PEON stream, next to the regular entries.
+///
+/// Extension entries are written with [crate::PrefixEncoder::write_extension]. Decoders return
+/// them from [crate::PrefixDecoder::read_item] once their kind is registered with
+/// [crate::PrefixDecoder::register_extension]. Entries of unregistered kinds are skipped if
+/// they're optional, or fail decoding with [Error::UnsupportedExtension] otherwise.
+pub trait Extension: Sized {
+    /// Id of the extension kind, at most `0x7FFF`. Ids below [MIN_USER_ID] are reserved for
+    /// extensions defined by PEON itself.
+    const ID: u16;
+    /// Whether decoders that don't know this kind can safely skip its entries.
+    const OPTIONAL: bool;
+
+    /// Appends the payload of the extension entry to `buf`. Payload can't be longer than
+    /// 64KiB.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes the payload of the extension entry. Malformed payloads should be reported as
+    /// [Error::InvalidExtension].
+    fn decode(payload: &[u8]) -> Result<Self, Error>;
+}
+
+/// Item of a PEON stream returned by [crate::PrefixDecoder::read_item].
+#[derive(Debug)]
+pub enum DecodedItem<'a> {
+    Entry(Path<'a>, &'a [u8]),
+    Extension(ExtensionEntry<'a>),
+}
+
+/// Extension entry of a kind registered with [crate::PrefixDecoder::register_extension].
+#[derive(Debug, Clone, Copy)]
+pub struct ExtensionEntry<'a> {
+    pub id: u16,
+    pub optional: bool,
+    pub payload: &'a [u8],
+}
+
+impl<'a> ExtensionEntry<'a> {
+    /// Decodes the payload as extension `E`, returns `None` if the entry is of other kind.
+    pub fn decode<E: Extension>(&self) -> Result<Option<E>, Error> {
+        if self.id == E::ID {
+            E::decode(self.payload).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DecodedItem, Extension, MIN_USER_ID};
+    use crate::{Error, PathBuf, PathSegment, PrefixDecoder, PrefixEncoder};
+
+    #[derive(Debug, PartialEq)]
+    struct Timestamp(u64);
+
+    impl Extension for Timestamp {
+        const ID: u16 = MIN_USER_ID;
+        const OPTIONAL: bool = true;
+
+        fn encode(&self, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(&self.0.to_be_bytes());
+        }
+
+        fn decode(payload: &[u8]) -> Result<Self, Error> {
+            let bytes = payload
+                .try_into()
+                .map_err(|_| Error::InvalidExtension(Self::ID))?;
+            Ok(Timestamp(u64::from_be_bytes(bytes)))
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Schema(String);
+
+    impl Extension for Schema {
+        const ID: u16 = MIN_USER_ID + 1;
+        const OPTIONAL: bool = false;
+
+        fn encode(&self, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(self.0.as_bytes());
+        }
+
+        fn decode(payload: &[u8]) -> Result<Self, Error> {
+            let schema =
+                std::str::from_utf8(payload).map_err(|_| Error::InvalidExtension(Self::ID))?;
+            Ok(Schema(schema.to_string()))
+        }
+    }
+
+    fn user(index: u64, field: &str) -> PathBuf<Vec<u8>> {
+        PathBuf::from_iter([PathSegment::Key("users"), index.into(), field.into()])
+    }
+
+    fn sample(schema: bool) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        if schema {
+            encoder
+                .write_extension(&Schema("users/v1".to_string()))
+                .unwrap();
+        }
+        encoder.write_next(user(0, "name").as_ref(), b"a").unwrap();
+        encoder.write_extension(&Timestamp(1_700_000_000)).unwrap();
+        encoder.write_next(user(0, "nick").as_ref(), b"b").unwrap();
+        encoder.write_next(user(1, "name").as_ref(), b"c").unwrap();
+        buf
+    }
+
+    #[test]
+    fn extension_items() {
+        let buf = sample(true);
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        decoder.register_extension::<Timestamp>();
+        decoder.register_extension::<Schema>();
+        let mut items = Vec::new();
+        while let Some(item) = decoder.read_item().unwrap() {
+            match item {
+                DecodedItem::Entry(path, value) => items.push(format!("{path}={value:?}")),
+                DecodedItem::Extension(ext) => {
+                    if let Some(Timestamp(ts)) = ext.decode::<Timestamp>().unwrap() {
+                        items.push(format!("timestamp {ts}"));
+                    }
+                    if let Some(Schema(schema)) = ext.decode::<Schema>().unwrap() {
+                        items.push(format!("schema {schema}"));
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            items,
+            vec![
+                "schema users/v1",
+                "$.users[0].name=[97]",
+                "timestamp 1700000000",
+                "$.users[0].nick=[98]",
+                "$.users[1].name=[99]",
+            ]
+        );
+    }
+
+    #[test]
+    fn extension_unregistered() {
+        let expected = vec!["$.users[0].name", "$.users[0].nick", "$.users[1].name"];
+
+        // optional extensions are skipped by both read_next and read_item
+        let buf = sample(false);
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        let mut paths = Vec::new();
+        while let Some(item) = decoder.read_item().unwrap() {
+            match item {
+                DecodedItem::Entry(path, _) => paths.push(path.to_string()),
+                DecodedItem::Extension(ext) => panic!("unexpected extension {ext:?}"),
+            }
+        }
+        assert_eq!(paths, expected);
+
+        // mandatory ones must be registered
+        let buf = sample(true);
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        let err = decoder.read_next().unwrap_err();
+        assert!(matches!(err, Error::UnsupportedExtension(Schema::ID)));
+
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        decoder.register_extension::<Schema>();
+        let mut paths = Vec::new();
+        while let Some((path, _)) = decoder.read_next().unwrap() {
+            paths.push(path.to_string());
+        }
+        assert_eq!(paths, expected);
+    }
+
+    /// Extension pretending to be one of PEON itself.
+    struct Reserved<const ID: u16>;
+
+    impl<const ID: u16> Extension for Reserved<ID> {
+        const ID: u16 = ID;
+        const OPTIONAL: bool = true;
+
+        fn encode(&self, _buf: &mut Vec<u8>) {}
+
+        fn decode(_payload: &[u8]) -> Result<Self, Error> {
+            Ok(Reserved)
+        }
+    }
+
+    #[test]
+    fn extension_reserved_ids() {
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        for err in [
+            encoder.write_extension(&Reserved::<1>).unwrap_err(),
+            encoder
+                .write_extension(&Reserved::<{ MIN_USER_ID - 1 }>)
+                .unwrap_err(),
+            encoder.write_extension(&Reserved::<0x8000>).unwrap_err(),
+        ] {
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+        encoder.write_extension(&Reserved::<0x7FFF>).unwrap();
+        assert_eq!(buf.len(), 6);
+    }
+}
@@ -0,0 +1,210 @@
+use crate::{Error, Extension};
+
+/// Magic bytes identifying a PEON stream.
+pub(crate) const MAGIC: &[u8; 4] = b"PEON";
+
+/// Id of the extension entry carrying the stream header.
+pub(crate) const EXT_HEADER: u16 = 1;
+
+/// Preamble of a PEON stream, written by [crate::PrefixEncoder::with_header] as an optional
+/// extension entry in front of all other entries.
+///
+/// Decoders which don't know about headers simply skip it, while [crate::PrefixDecoder] checks
+/// the magic bytes and refuses streams using a newer format version or flags it doesn't
+/// understand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamHeader {
+    pub version: u8,
+    pub flags: u16,
+}
+
+impl StreamHeader {
+    /// Current version of the format.
+    pub const VERSION: u8 = 1;
+
+    /// Values are encoded using tagged scalars of [crate::json].
+    pub const FLAG_JSON_VALUES: u16 = 0b0000_0001;
+
+    /// Stream is split into blocks followed by checksums, see
+    /// [crate::PrefixEncoder::with_checksums]. Decoders reject streams ending with a block
+    /// without a checksum.
+    pub const FLAG_CHECKSUMS: u16 = 0b0000_0010;
+
+    /// Stream contains blocks of entries compressed with LZ4, see
+    /// [crate::PrefixEncoder::with_compression]. Decoders built without the `lz4` feature
+    /// reject such streams upfront instead of failing on the first compressed block.
+    pub const FLAG_COMPRESSION: u16 = 0b0000_0100;
+
+    /// All flags known to this version of the decoder.
+    #[cfg(feature = "lz4")]
+    const KNOWN_FLAGS: u16 = Self::FLAG_JSON_VALUES | Self::FLAG_CHECKSUMS | Self::FLAG_COMPRESSION;
+    #[cfg(not(feature = "lz4"))]
+    const KNOWN_FLAGS: u16 = Self::FLAG_JSON_VALUES | Self::FLAG_CHECKSUMS;
+
+    /// Header of the current format version with given flags.
+    pub fn new(flags: u16) -> Self {
+        Self {
+            version: Self::VERSION,
+            flags,
+        }
+    }
+
+    pub fn has_flag(&self, flag: u16) -> bool {
+        self.flags & flag == flag
+    }
+}
+
+impl Extension for StreamHeader {
+    const ID: u16 = EXT_HEADER;
+    const OPTIONAL: bool = true;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(MAGIC);
+        buf.push(self.version);
+        buf.extend_from_slice(&self.flags.to_be_bytes());
+    }
+
+    /// Decodes and validates the header payload. Bytes following the known fields are ignored,
+    /// so that future versions can extend it.
+    fn decode(payload: &[u8]) -> Result<Self, Error> {
+        if payload.len() < 7 || &payload[..4] != MAGIC {
+            return Err(Error::InvalidHeader);
+        }
+        let header = Self {
+            version: payload[4],
+            flags: u16::from_be_bytes([payload[5], payload[6]]),
+        };
+        if header.version > Self::VERSION {
+            return Err(Error::UnsupportedVersion(header.version));
+        }
+        if header.flags & !Self::KNOWN_FLAGS != 0 {
+            return Err(Error::UnsupportedFlags(header.flags & !Self::KNOWN_FLAGS));
+        }
+        Ok(header)
+    }
+}
+
+impl Default for StreamHeader {
+    fn default() -> Self {
+        Self::new(Self::FLAG_JSON_VALUES)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StreamHeader;
+    use crate::{Error, PathBuf, PathSegment, PrefixDecoder, PrefixEncoder};
+
+    fn encode(header: StreamHeader) -> Vec<u8> {
+        let a = PathBuf::from_iter([PathSegment::Key("a")]);
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::with_header(&mut buf, header).unwrap();
+        encoder.write_next(a.as_ref(), b"a").unwrap();
+        buf
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let buf = encode(StreamHeader::default());
+        assert_eq!(&buf[6..10], b"PEON");
+
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        let header = decoder.read_stream_header().unwrap();
+        assert_eq!(header, StreamHeader::new(StreamHeader::FLAG_JSON_VALUES));
+        assert!(header.has_flag(StreamHeader::FLAG_JSON_VALUES));
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.to_string(), "$.a");
+        assert_eq!(value, b"a");
+
+        // header is checked even if it's not explicitly asked for
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        assert_eq!(decoder.header(), None);
+        assert!(decoder.read_next().unwrap().is_some());
+        assert_eq!(decoder.header(), Some(header));
+        assert!(decoder.read_next().unwrap().is_none());
+    }
+
+    #[test]
+    fn header_missing() {
+        let mut buf = Vec::new();
+        PrefixEncoder::new(&mut buf)
+            .write_next(&[0, b'a'], b"a")
+            .unwrap();
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        assert!(matches!(
+            decoder.read_stream_header(),
+            Err(Error::MissingHeader)
+        ));
+        assert!(matches!(
+            decoder.read_stream_header(),
+            Err(Error::MissingHeader)
+        ));
+        // the first entry isn't consumed by looking for the header
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.to_string(), "$.a");
+        assert_eq!(value, b"a");
+        assert!(decoder.read_next().unwrap().is_none());
+
+        let garbage = b"{\"not\": \"peon\"}";
+        let mut decoder = PrefixDecoder::new(&garbage[..]);
+        assert!(matches!(
+            decoder.read_stream_header(),
+            Err(Error::MissingHeader)
+        ));
+
+        let mut decoder = PrefixDecoder::new(&[][..]);
+        assert!(matches!(
+            decoder.read_stream_header(),
+            Err(Error::MissingHeader)
+        ));
+    }
+
+    #[test]
+    fn header_rejected() {
+        let mut wrong_magic = encode(StreamHeader::default());
+        wrong_magic[6] = b'X';
+        let newer = encode(StreamHeader {
+            version: StreamHeader::VERSION + 1,
+            flags: 0,
+        });
+        let unknown_flags = encode(StreamHeader::new(0x8000));
+        for (buf, expected) in [
+            (wrong_magic, "malformed PEON stream header"),
+            (newer, "unsupported PEON format version: 2"),
+            (unknown_flags, "unsupported PEON stream flags: 0x8000"),
+        ] {
+            let mut decoder = PrefixDecoder::new(buf.as_slice());
+            let err = decoder.read_next().unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn header_compression_flag() {
+        let buf = encode(StreamHeader::new(StreamHeader::FLAG_COMPRESSION));
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        let header = decoder.read_stream_header();
+        if cfg!(feature = "lz4") {
+            assert!(header.unwrap().has_flag(StreamHeader::FLAG_COMPRESSION));
+        } else {
+            assert!(matches!(header, Err(Error::UnsupportedFlags(0b100))));
+        }
+    }
+
+    #[test]
+    fn header_skipped_when_not_at_start() {
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        encoder.write_next(&[0, b'a'], b"a").unwrap();
+        encoder.write_builtin(&StreamHeader::default()).unwrap();
+        encoder.write_next(&[0, b'b'], b"b").unwrap();
+
+        let mut decoder = PrefixDecoder::new(buf.as_slice());
+        let mut keys = Vec::new();
+        while let Some((path, _)) = decoder.read_next().unwrap() {
+            keys.push(path.to_string());
+        }
+        assert_eq!(keys, vec!["$.a", "$.b"]);
+        assert_eq!(decoder.header(), None);
+    }
+}
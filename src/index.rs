@@ -0,0 +1,320 @@
+use crate::{Error, Extension};
+
+/// Id of the extension entries carrying the restart index.
+pub(crate) const EXT_INDEX: u16 = 3;
+
+/// Id of the extension entry closing an indexed stream.
+pub(crate) const EXT_INDEX_TRAILER: u16 = 4;
+
+/// Optional extension entry written in front of every restart point, so that decoders can find
+/// them by scanning the stream from an arbitrary position. It's an entry of kind 5 with 16 bytes
+/// of payload, which contains bytes never appearing in UTF-8 text, so that false matches are
+/// unlikely outside of binary values. Those are told apart by the entries following the marker.
+pub(crate) const RESTART_MARKER: [u8; 22] = *b"\x80\0\0\x10\x80\x05\xFF\xFEPEON-RESTART\xFE\xFF";
+
+/// Magic bytes ending an indexed stream.
+const INDEX_MAGIC: &[u8; 4] = b"PIDX";
+
+/// Length of the trailer entry, including its header.
+pub(crate) const TRAILER_LEN: usize = 6 + 12;
+
+/// Restart point of a stream, an entry written with its full key behind [RESTART_MARKER].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RestartPoint {
+    pub key: Vec<u8>,
+    /// Position of the marker within the stream.
+    pub offset: u64,
+}
+
+/// Splits the index into payloads of extension entries, each of them fitting into 64KiB.
+///
+/// Every restart point is stored as its offset, followed by length of its key and the key.
+pub(crate) fn encode_index(index: &[RestartPoint]) -> Vec<Vec<u8>> {
+    let mut payloads = Vec::new();
+    let mut payload = Vec::new();
+    for point in index {
+        if payload.len() + 10 + point.key.len() > u16::MAX as usize {
+            payloads.push(std::mem::take(&mut payload));
+        }
+        payload.extend_from_slice(&point.offset.to_be_bytes());
+        payload.extend_from_slice(&(point.key.len() as u16).to_be_bytes());
+        payload.extend_from_slice(&point.key);
+    }
+    if !payload.is_empty() {
+        payloads.push(payload);
+    }
+    payloads
+}
+
+/// Decodes restart points stored in `payload` of an index entry, appending them to `index`.
+pub(crate) fn decode_index(mut payload: &[u8], index: &mut Vec<RestartPoint>) -> Result<(), Error> {
+    while !payload.is_empty() {
+        let Some((offset, rest)) = payload.split_first_chunk::<8>() else {
+            return Err(Error::InvalidIndex);
+        };
+        let Some((key_len, rest)) = rest.split_first_chunk::<2>() else {
+            return Err(Error::InvalidIndex);
+        };
+        let key_len = u16::from_be_bytes(*key_len) as usize;
+        if rest.len() < key_len {
+            return Err(Error::InvalidIndex);
+        }
+        let (key, rest) = rest.split_at(key_len);
+        index.push(RestartPoint {
+            key: key.to_vec(),
+            offset: u64::from_be_bytes(*offset),
+        });
+        payload = rest;
+    }
+    Ok(())
+}
+
+/// Last entry of a stream written by [crate::PrefixEncoder::with_index], pointing back at the
+/// index, so that readers able to seek can find it from the end of the stream.
+///
+/// Trailer isn't a part of any checksummed block, as it follows the last checksum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct IndexTrailer {
+    /// Position of the first index entry within the stream.
+    pub index_offset: u64,
+}
+
+impl IndexTrailer {
+    /// Recognizes the trailer in the last [TRAILER_LEN] bytes of a stream.
+    pub fn from_tail(tail: &[u8; TRAILER_LEN]) -> Option<Self> {
+        let (header, payload) = tail.split_at(6);
+        let [id_hi, id_lo] = EXT_INDEX_TRAILER.to_be_bytes();
+        if header != [0x80, 0, 0, 12, 0x80 | id_hi, id_lo] {
+            return None;
+        }
+        Self::decode(payload).ok()
+    }
+}
+
+impl Extension for IndexTrailer {
+    const ID: u16 = EXT_INDEX_TRAILER;
+    const OPTIONAL: bool = true;
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.index_offset.to_be_bytes());
+        buf.extend_from_slice(INDEX_MAGIC);
+    }
+
+    fn decode(payload: &[u8]) -> Result<Self, Error> {
+        match payload.split_first_chunk::<8>() {
+            Some((offset, magic)) if magic == INDEX_MAGIC => Ok(Self {
+                index_offset: u64::from_be_bytes(*offset),
+            }),
+            _ => Err(Error::InvalidIndex),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RestartPoint, decode_index, encode_index};
+    use crate::{Error, PathBuf, PathSegment, PrefixDecoder, PrefixEncoder, StreamHeader};
+    use std::io::Cursor;
+
+    fn user(index: u64, field: &str) -> PathBuf<Vec<u8>> {
+        PathBuf::from_iter([PathSegment::Key("users"), index.into(), field.into()])
+    }
+
+    fn sample(users: u64, checksums: bool) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let flags = if checksums {
+            StreamHeader::FLAG_CHECKSUMS
+        } else {
+            0
+        };
+        let mut encoder = PrefixEncoder::with_header(&mut buf, StreamHeader::new(flags))
+            .unwrap()
+            .with_index(16);
+        if checksums {
+            encoder = encoder.with_checksums(10);
+        }
+        for i in 0..users {
+            for field in ["age", "name"] {
+                let value = format!("{field}-{i}");
+                encoder
+                    .write_next(user(i, field).as_ref(), value.as_bytes())
+                    .unwrap();
+            }
+        }
+        encoder.finish().unwrap();
+        buf
+    }
+
+    fn next_value<R: AsRef<[u8]>>(decoder: &mut PrefixDecoder<Cursor<R>>) -> Option<String> {
+        let (_, value) = decoder.read_next().unwrap()?;
+        Some(String::from_utf8(value.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn index_round_trip() {
+        let index: Vec<_> = (0..10_000u64)
+            .map(|i| RestartPoint {
+                key: user(i, "name").as_bytes().to_vec(),
+                offset: i * 1000,
+            })
+            .collect();
+        let payloads = encode_index(&index);
+        assert!(payloads.len() > 1);
+        let mut decoded = Vec::new();
+        for payload in &payloads {
+            assert!(payload.len() <= u16::MAX as usize);
+            decode_index(payload, &mut decoded).unwrap();
+        }
+        assert_eq!(decoded, index);
+
+        let res = decode_index(&payloads[0][..20], &mut decoded);
+        assert!(matches!(res, Err(Error::InvalidIndex)));
+    }
+
+    #[test]
+    fn seek_to_path() {
+        for checksums in [false, true] {
+            let buf = sample(1000, checksums);
+            let mut decoder = PrefixDecoder::new(Cursor::new(buf));
+            for i in [998, 0, 500, 7, 8, 640, 641] {
+                decoder.seek_to(&user(i, "name").as_path()).unwrap();
+                assert_eq!(next_value(&mut decoder), Some(format!("name-{i}")));
+                assert_eq!(next_value(&mut decoder), Some(format!("age-{}", i + 1)));
+            }
+
+            // missing paths position the decoder at the following entry
+            decoder.seek_to(&user(42, "email").as_path()).unwrap();
+            assert_eq!(next_value(&mut decoder), Some("name-42".to_string()));
+            decoder.seek_to(&user(2000, "age").as_path()).unwrap();
+            assert_eq!(next_value(&mut decoder), None);
+
+            // the whole stream is still readable from the start
+            let buf = sample(1000, checksums);
+            let mut decoder = PrefixDecoder::new(Cursor::new(buf));
+            let mut count = 0;
+            while decoder.read_next().unwrap().is_some() {
+                count += 1;
+            }
+            assert_eq!(count, 2000);
+        }
+    }
+
+    #[test]
+    fn seek_to_without_index() {
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        encoder.write_next(user(0, "name").as_ref(), b"a").unwrap();
+        encoder.finish().unwrap();
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf));
+        let res = decoder.seek_to(&user(0, "name").as_path());
+        assert!(matches!(res, Err(Error::MissingIndex)), "{res:?}");
+
+        let mut corrupted = sample(100, false);
+        let len = corrupted.len();
+        // index offset pointing past the index
+        corrupted[len - 7] ^= 0x40;
+        let mut decoder = PrefixDecoder::new(Cursor::new(corrupted));
+        let res = decoder.seek_to(&user(0, "name").as_path());
+        assert!(matches!(res, Err(Error::InvalidIndex)), "{res:?}");
+    }
+
+    /// Decodes entries of the byte range of `buf` as values.
+    fn decode_range(buf: &[u8], range: std::ops::Range<u64>) -> Vec<String> {
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf));
+        let end = decoder
+            .seek_to_restart(range.end)
+            .unwrap()
+            .unwrap_or(u64::MAX);
+        let mut values = Vec::new();
+        if decoder.seek_to_restart(range.start).unwrap().is_none() {
+            return values;
+        }
+        while let Some(value) = next_value(&mut decoder) {
+            if decoder.entry_position() >= end {
+                break;
+            }
+            values.push(value);
+        }
+        values
+    }
+
+    #[test]
+    fn restart_points_parallel_decoding() {
+        let mut buf = Vec::new();
+        let header = StreamHeader::new(StreamHeader::FLAG_CHECKSUMS);
+        let mut encoder = PrefixEncoder::with_header(&mut buf, header)
+            .unwrap()
+            .with_restart_interval(37)
+            .with_checksums(50);
+        let mut expected = Vec::new();
+        for i in 0..5000 {
+            let value = format!("name-{i}");
+            encoder
+                .write_next(user(i, "name").as_ref(), value.as_bytes())
+                .unwrap();
+            expected.push(value);
+        }
+        encoder.finish().unwrap();
+
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf.as_slice()));
+        let first = decoder.seek_to_restart(0).unwrap().unwrap();
+        assert_eq!(next_value(&mut decoder), Some("name-0".to_string()));
+        let second = decoder.seek_to_restart(first + 1).unwrap().unwrap();
+        assert_eq!(next_value(&mut decoder), Some("name-37".to_string()));
+        assert_eq!(
+            decoder.entry_position(),
+            second + super::RESTART_MARKER.len() as u64
+        );
+        assert_eq!(
+            decoder.seek_to_restart(buf.len() as u64 - 100).unwrap(),
+            None
+        );
+        assert!(decoder.read_next().unwrap().is_none());
+
+        let len = buf.len() as u64;
+        let ranges = 7;
+        let decoded: Vec<String> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..ranges)
+                .map(|i| {
+                    let range = len * i / ranges..len * (i + 1) / ranges;
+                    let buf = buf.as_slice();
+                    scope.spawn(move || decode_range(buf, range))
+                })
+                .collect();
+            threads
+                .into_iter()
+                .flat_map(|thread| thread.join().unwrap())
+                .collect()
+        });
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn restart_marker_inside_blob() {
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf).with_restart_interval(4);
+        let mut blob = b"binary ".to_vec();
+        blob.extend_from_slice(&super::RESTART_MARKER);
+        blob.extend_from_slice(b" data");
+        for i in 0..8 {
+            if i == 2 {
+                encoder
+                    .write_blob(user(i, "avatar").as_ref(), blob.as_slice())
+                    .unwrap();
+            }
+            let value = format!("name-{i}");
+            encoder
+                .write_next(user(i, "name").as_ref(), value.as_bytes())
+                .unwrap();
+        }
+        encoder.finish().unwrap();
+
+        let mut decoder = PrefixDecoder::new(Cursor::new(buf.as_slice()));
+        let first = decoder.seek_to_restart(0).unwrap().unwrap();
+        assert_eq!(first, 0);
+        assert_eq!(next_value(&mut decoder), Some("name-0".to_string()));
+        // marker within the blob is skipped
+        decoder.seek_to_restart(first + 1).unwrap().unwrap();
+        assert_eq!(next_value(&mut decoder), Some("name-3".to_string()));
+    }
+}
@@ -0,0 +1,200 @@
+use std::cmp::Ordering;
+
+/// Exact value of a JSON number, normalized for comparison: `0.d₁d₂…dₙ × 10^exponent`.
+///
+/// Both leading and trailing zeros are stripped from `digits`, so each number has a single
+/// representation regardless of how it was written, i.e. `1.50`, `15e-1` and `0.15E1` are all
+/// the same decimal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Decimal {
+    negative: bool,
+    /// Significant digits, without leading and trailing zeros. Empty for zero.
+    digits: Vec<u8>,
+    exponent: i64,
+}
+
+impl Decimal {
+    /// Parses a number following the JSON number grammar.
+    pub fn parse(text: &str) -> Option<Self> {
+        let bytes = text.as_bytes();
+        let mut pos = 0;
+        let negative = bytes.first() == Some(&b'-');
+        if negative {
+            pos += 1;
+        }
+
+        let int_start = pos;
+        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        let int_part = &bytes[int_start..pos];
+        if int_part.is_empty() || (int_part.len() > 1 && int_part[0] == b'0') {
+            return None;
+        }
+
+        let mut frac_part: &[u8] = &[];
+        if bytes.get(pos) == Some(&b'.') {
+            let frac_start = pos + 1;
+            pos = frac_start;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                pos += 1;
+            }
+            frac_part = &bytes[frac_start..pos];
+            if frac_part.is_empty() {
+                return None;
+            }
+        }
+
+        let mut exponent: i64 = 0;
+        if matches!(bytes.get(pos), Some(b'e' | b'E')) {
+            pos += 1;
+            let exp_negative = match bytes.get(pos) {
+                Some(b'-') => {
+                    pos += 1;
+                    true
+                }
+                Some(b'+') => {
+                    pos += 1;
+                    false
+                }
+                _ => false,
+            };
+            let exp_start = pos;
+            while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                // exponents beyond i32 range are far outside of anything meaningful, saturate
+                // instead of overflowing
+                exponent = exponent
+                    .saturating_mul(10)
+                    .saturating_add((bytes[pos] - b'0') as i64)
+                    .min(i32::MAX as i64);
+                pos += 1;
+            }
+            if pos == exp_start {
+                return None;
+            }
+            if exp_negative {
+                exponent = -exponent;
+            }
+        }
+        if pos != bytes.len() {
+            return None;
+        }
+
+        let mut digits: Vec<u8> = int_part.iter().chain(frac_part).map(|b| b - b'0').collect();
+        exponent += int_part.len() as i64;
+        let leading = digits.iter().take_while(|&&d| d == 0).count();
+        digits.drain(..leading);
+        exponent -= leading as i64;
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        if digits.is_empty() {
+            return Some(Decimal {
+                negative: false,
+                digits,
+                exponent: 0,
+            });
+        }
+        Some(Decimal {
+            negative,
+            digits,
+            exponent,
+        })
+    }
+
+    pub fn from_i64(value: i64) -> Self {
+        Self::parse(&value.to_string()).unwrap()
+    }
+
+    pub fn from_u64(value: u64) -> Self {
+        Self::parse(&value.to_string()).unwrap()
+    }
+
+    /// Converts a float using its shortest representation which round trips back to the same
+    /// float, i.e. `0.1f64` becomes exactly `0.1`. Returns `None` for NaN and infinities.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if value.is_finite() {
+            Self::parse(&format!("{value:e}"))
+        } else {
+            None
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    fn cmp_magnitude(&self, other: &Self) -> Ordering {
+        match (self.is_zero(), other.is_zero()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => self
+                .exponent
+                .cmp(&other.exponent)
+                .then_with(|| self.digits.cmp(&other.digits)),
+        }
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Decimal;
+
+    #[test]
+    fn decimal_parse_normalizes() {
+        let same = ["1.50", "15e-1", "0.15E1", "1.5", "150e-2", "0.0015e+3"];
+        for text in same {
+            assert_eq!(Decimal::parse(text), Decimal::parse("1.5"), "{text}");
+        }
+        assert_eq!(Decimal::parse("-0.0"), Decimal::parse("0"));
+        assert_eq!(Decimal::from_f64(0.1), Decimal::parse("0.1"));
+        assert_eq!(Some(Decimal::from_i64(-1200)), Decimal::parse("-1.2e3"));
+        for invalid in ["", "-", "01", "1.", ".5", "1e", "1e+", "+1", "1.5x", "NaN"] {
+            assert_eq!(Decimal::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn decimal_ordering() {
+        let sorted = [
+            "-1e400",
+            "-12345678901234567890.5",
+            "-2",
+            "-1.99999999999999999999",
+            "-0.001",
+            "0",
+            "1e-400",
+            "0.1",
+            "0.10000000000000000001",
+            "1",
+            "9.99",
+            "10",
+            "18446744073709551616",
+            "1e400",
+        ];
+        for pair in sorted.windows(2) {
+            let a = Decimal::parse(pair[0]).unwrap();
+            let b = Decimal::parse(pair[1]).unwrap();
+            assert!(a < b, "{} < {}", pair[0], pair[1]);
+            assert!(b > a, "{} > {}", pair[1], pair[0]);
+        }
+    }
+}
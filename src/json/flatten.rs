@@ -1,5 +1,6 @@
-use crate::json::{TAG_BOOL_FALSE, TAG_BOOL_TRUE, TAG_FLOAT, TAG_INTEGER, TAG_NULL, TAG_STRING};
-use crate::{PathBuf, size_hint};
+use crate::PathBuf;
+use crate::json::scalar::{encode_bool, encode_null, encode_number, encode_str};
+use crate::json::{TAG_EMPTY_ARRAY, TAG_EMPTY_OBJECT};
 use smallvec::{SmallVec, smallvec};
 
 pub trait Flatten {
@@ -13,94 +14,257 @@ pub trait Flatten {
 
 impl Flatten for serde_json::Value {
     type Value = super::Value;
-    type Iter = Vec<(PathBuf<Vec<u8>>, Self::Value)>;
+    type Iter = FlattenIter;
 
     fn flatten(self, chunk_size: usize) -> Self::Iter {
-        let mut acc = Vec::new();
-        let mut path = PathBuf::new(Vec::new());
-
-        flatten_inner(chunk_size, &self, &mut path, &mut acc);
-
-        acc
+        FlattenIter {
+            chunk_size,
+            path: PathBuf::new(Vec::new()),
+            stack: vec![Frame::Value(self)],
+        }
     }
 }
 
-fn flatten_inner(
+/// Lazy iterator over the entries of a flattened `serde_json::Value`.
+///
+/// Objects and arrays are traversed depth-first using an explicit stack, while a single path
+/// buffer is shared by all entries - it's extended when descending into a value and truncated
+/// back once the value has been visited.
+pub struct FlattenIter {
     chunk_size: usize,
-    value: &serde_json::Value,
-    path_buf: &mut PathBuf<Vec<u8>>,
-    acc: &mut Vec<(PathBuf<Vec<u8>>, super::Value)>,
-) {
-    match value {
-        serde_json::Value::Null => {
-            acc.push((path_buf.clone(), smallvec![TAG_NULL]));
-        }
-        serde_json::Value::Array(array) => {
-            for (index, item) in array.iter().enumerate() {
-                let mut path_buf = path_buf.clone();
-                // Push the current index to the path
-                path_buf.push_index(index as u64).unwrap();
-                // Recursively flatten the item
-                flatten_inner(chunk_size, item, &mut path_buf, acc);
+    path: PathBuf<Vec<u8>>,
+    stack: Vec<Frame>,
+}
+
+enum Frame {
+    /// Value that has not been visited yet.
+    Value(serde_json::Value),
+    Array {
+        path_len: usize,
+        index: u64,
+        iter: std::vec::IntoIter<serde_json::Value>,
+    },
+    Object {
+        path_len: usize,
+        iter: serde_json::map::IntoIter,
+    },
+    /// String split into continuation entries.
+    Chunks {
+        path_len: usize,
+        value: String,
+        offset: usize,
+    },
+}
+
+impl FlattenIter {
+    /// Visits a `value` stored under the current path. Returns an entry for scalar values,
+    /// while objects, arrays and chunked strings are pushed onto the stack.
+    fn visit(&mut self, value: serde_json::Value) -> Option<(PathBuf<Vec<u8>>, super::Value)> {
+        let path_len = self.path.as_bytes().len();
+        let value = match value {
+            serde_json::Value::Null => encode_null(),
+            serde_json::Value::Bool(v) => encode_bool(v),
+            serde_json::Value::Number(v) => encode_number(&v),
+            serde_json::Value::String(value) => {
+                if value.len() > self.chunk_size {
+                    self.stack.push(Frame::Chunks {
+                        path_len,
+                        value,
+                        offset: 0,
+                    });
+                    return None;
+                }
+                encode_str(&value)
             }
-        }
-        serde_json::Value::Object(array) => {
-            for (key, item) in array.iter() {
-                let mut path_buf = path_buf.clone();
-                // Push the current key to the path
-                path_buf.push_key(key).unwrap();
-                // Recursively flatten the item
-                flatten_inner(chunk_size, item, &mut path_buf, acc);
+            serde_json::Value::Array(array) if array.is_empty() => smallvec![TAG_EMPTY_ARRAY],
+            serde_json::Value::Array(array) => {
+                self.stack.push(Frame::Array {
+                    path_len,
+                    index: 0,
+                    iter: array.into_iter(),
+                });
+                return None;
             }
-        }
-        serde_json::Value::String(value) => {
-            let bytes = value.as_bytes();
-            if bytes.len() <= chunk_size {
-                let mut buf = super::Value::with_capacity(chunk_size + 1);
-                buf.push(TAG_STRING);
-                buf.extend_from_slice(bytes);
-                acc.push((path_buf.clone(), buf));
-            } else {
-                let mut index = 0usize;
-                while index < bytes.len() {
-                    let mut path_buf = path_buf.clone();
-                    // Push the current chunk to the path
-                    path_buf.push_index(index as u64).unwrap();
-                    path_buf.push_continued().unwrap();
-                    let path_len = path_buf.as_bytes().len();
-                    let chunk_len = (chunk_size - path_len - 6).min(bytes.len() - index);
-                    let chunk = &bytes[index..(index + chunk_len)];
-                    // Push the chunked value
-                    acc.push((path_buf, SmallVec::from_slice(chunk)));
-                    index += chunk_len;
+            serde_json::Value::Object(object) if object.is_empty() => smallvec![TAG_EMPTY_OBJECT],
+            serde_json::Value::Object(object) => {
+                self.stack.push(Frame::Object {
+                    path_len,
+                    iter: object.into_iter(),
+                });
+                return None;
+            }
+        };
+        Some((self.path.clone(), value))
+    }
+}
+
+impl Iterator for FlattenIter {
+    type Item = (PathBuf<Vec<u8>>, super::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match self.stack.last_mut()? {
+                Frame::Value(_) => match self.stack.pop() {
+                    Some(Frame::Value(value)) => Some(value),
+                    _ => unreachable!(),
+                },
+                Frame::Array {
+                    path_len,
+                    index,
+                    iter,
+                } => {
+                    self.path.truncate(*path_len);
+                    let item = iter.next();
+                    if item.is_some() {
+                        // Push the current index to the path
+                        self.path.push_index(*index).unwrap();
+                        *index += 1;
+                    }
+                    item
+                }
+                Frame::Object { path_len, iter } => {
+                    self.path.truncate(*path_len);
+                    iter.next().map(|(key, item)| {
+                        // Push the current key to the path
+                        self.path.push_key(&key).unwrap();
+                        item
+                    })
+                }
+                Frame::Chunks {
+                    path_len,
+                    value,
+                    offset,
+                } => {
+                    self.path.truncate(*path_len);
+                    if *offset < value.len() {
+                        let (chunk, next_offset) =
+                            next_chunk(self.chunk_size, value, *offset, &mut self.path);
+                        *offset = next_offset;
+                        return Some((self.path.clone(), chunk));
+                    }
+                    None
+                }
+            };
+            match next {
+                Some(value) => {
+                    if let Some(entry) = self.visit(value) {
+                        return Some(entry);
+                    }
+                }
+                None => {
+                    // all children of the top frame have been visited
+                    self.stack.pop();
                 }
             }
         }
-        serde_json::Value::Number(v) => {
-            if let Some(v) = value.as_i64() {
-                let zigzag = if v < 0 {
-                    (v << 1) as u64 - 1
-                } else {
-                    (v as u64) << 1
-                };
-                let byte_len = size_hint(zigzag);
-                let mut buf = smallvec![TAG_INTEGER | byte_len];
-                let bytes = zigzag.to_be_bytes();
-                let slice = &bytes[(8 - byte_len as usize)..];
-                buf.extend_from_slice(slice);
-                acc.push((path_buf.clone(), buf));
-            } else if let Some(v) = v.as_f64() {
-                let mut buf = smallvec![TAG_FLOAT];
-                buf.extend_from_slice(&v.to_le_bytes());
-                acc.push((path_buf.clone(), buf));
-            } else {
-                panic!("Unsupported number type");
-            }
+    }
+}
+
+/// Emits a string value stored under `path_buf`. Strings longer than `chunk_size` are split
+/// into continuation entries (`path[offset]..`) carrying raw string bytes.
+pub(crate) fn flatten_str<E>(
+    chunk_size: usize,
+    value: &str,
+    path_buf: &mut PathBuf<Vec<u8>>,
+    mut emit: impl FnMut(&PathBuf<Vec<u8>>, super::Value) -> Result<(), E>,
+) -> Result<(), E> {
+    if value.len() <= chunk_size {
+        emit(path_buf, encode_str(value))
+    } else {
+        let base_len = path_buf.as_bytes().len();
+        let mut offset = 0usize;
+        while offset < value.len() {
+            let (chunk, next_offset) = next_chunk(chunk_size, value, offset, path_buf);
+            let result = emit(path_buf, chunk);
+            path_buf.truncate(base_len);
+            result?;
+            offset = next_offset;
         }
-        serde_json::Value::Bool(v) => {
-            // For all other values, we push the current path and value
-            let value = smallvec![if *v { TAG_BOOL_TRUE } else { TAG_BOOL_FALSE }];
-            acc.push((path_buf.clone(), value));
+        Ok(())
+    }
+}
+
+/// Pushes the continuation segments of a string chunk starting at `offset` onto the path and
+/// returns the chunk together with the offset of the next one.
+///
+/// Chunks always end at a character boundary, so that each of them is a valid UTF-8 string on
+/// its own. A chunk holds at least one character, even if it doesn't fit into `chunk_size`.
+fn next_chunk(
+    chunk_size: usize,
+    value: &str,
+    offset: usize,
+    path_buf: &mut PathBuf<Vec<u8>>,
+) -> (super::Value, usize) {
+    let bytes = value.as_bytes();
+    // Push the current chunk to the path
+    path_buf.push_index(offset as u64).unwrap();
+    path_buf.push_continued().unwrap();
+    let path_len = path_buf.as_bytes().len();
+    let mut end = (offset + chunk_size.saturating_sub(path_len + 6)).min(bytes.len());
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    if end == offset {
+        // budget is too small for the next character, emit it whole
+        end += value[offset..].chars().next().map_or(0, char::len_utf8);
+    }
+    (SmallVec::from_slice(&bytes[offset..end]), end)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json::{Flatten, TAG_STRING};
+    use crate::{PathBuf, PathSegment};
+    use serde_json::json;
+
+    #[test]
+    fn flatten_lazy_order() {
+        let entries: Vec<_> = json!({
+            "b": [true, { "c": null }],
+            "a": "lorem ipsum dolor sit amet",
+        })
+        .flatten(20)
+        .map(|(path, _)| path.as_path().to_string())
+        .collect();
+        assert_eq!(
+            entries,
+            vec!["$.a[0]..", "$.a[10]..", "$.a[19]..", "$.b[0]", "$.b[1].c"]
+        );
+    }
+
+    #[test]
+    fn flatten_lazy_reuses_path() {
+        let mut iter = json!({ "users": [{ "name": "Alice" }, { "name": "Bob" }] }).flatten(100);
+        let (path, value) = iter.next().unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from_iter([PathSegment::Key("users"), 0u64.into(), "name".into()])
+        );
+        assert_eq!(
+            value.as_slice(),
+            &[TAG_STRING, b'A', b'l', b'i', b'c', b'e']
+        );
+        let (path, _) = iter.next().unwrap();
+        assert_eq!(
+            path,
+            PathBuf::from_iter([PathSegment::Key("users"), 1u64.into(), "name".into()])
+        );
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn flatten_chunks_at_char_boundaries() {
+        // 1, 2, 3 and 4 byte characters, so that every chunk size hits the middle of some
+        let text = "aé€🦀".repeat(20);
+        for chunk_size in 8..40 {
+            let mut actual = Vec::new();
+            for (path, value) in json!({ "t": text }).flatten(chunk_size) {
+                assert!(path.as_path().to_string().ends_with(".."));
+                let chunk = std::str::from_utf8(&value).expect("chunk is not valid UTF-8");
+                assert!(!chunk.is_empty());
+                actual.extend_from_slice(chunk.as_bytes());
+            }
+            assert_eq!(actual, text.as_bytes(), "chunk size {chunk_size}");
         }
     }
 }
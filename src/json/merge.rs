@@ -1,13 +1,15 @@
-use crate::json::TAG_INTEGER;
-use crate::{Path, PathSegment};
+use crate::json::scalar::{decode_decimal, decode_number, decode_str};
+use crate::json::{DecodeError, MAX_ARRAY_GAP, TAG_BYTES};
+use crate::{Error, Path, PathError, PathSegment};
 use std::cmp::Ordering;
 
-fn touch<'a>(root: &'a mut serde_json::Value, path: &Path) -> (&'a mut serde_json::Value, usize) {
+fn touch<'a>(
+    root: &'a mut serde_json::Value,
+    path: &Path,
+) -> Result<&'a mut serde_json::Value, Error> {
     let mut current = root;
-    let mut offset = 0;
-    let mut cont = false;
     for segment in path.iter() {
-        match segment.unwrap() {
+        match segment? {
             PathSegment::Key(key) => {
                 if !current.is_object() {
                     *current = serde_json::json!({});
@@ -18,34 +20,97 @@ fn touch<'a>(root: &'a mut serde_json::Value, path: &Path) -> (&'a mut serde_jso
                     .or_insert(serde_json::Value::Null);
             }
             PathSegment::Index(index) => {
-                if !current.is_array() && index == 0 {
+                if !current.is_array() {
                     *current = serde_json::json!([]);
                 }
                 let arr = current.as_array_mut().unwrap();
-                offset = index as usize;
-                if offset >= arr.len() {
-                    arr.resize(offset + 1, serde_json::Value::Null);
+                // missing elements are filled with nulls, as long as there are not too many
+                if index.saturating_sub(arr.len() as u64) > MAX_ARRAY_GAP {
+                    return Err(PathError::IndexGapTooLong(index).into());
                 }
-                current = arr.get_mut(offset).unwrap();
-            }
-            PathSegment::Cont => {
-                cont = true;
-                if !current.is_string() && offset == 0 {
-                    *current == serde_json::Value::String("".into());
+                let index = index as usize;
+                if index >= arr.len() {
+                    arr.resize(index + 1, serde_json::Value::Null);
                 }
+                current = arr.get_mut(index).unwrap();
+            }
+            // continuation entries are reassembled before touching their value
+            PathSegment::Cont => {}
+        }
+    }
+    Ok(current)
+}
+
+/// Value split into continuation entries, which is being reassembled.
+struct Chunks {
+    /// Path of the value.
+    path: Vec<u8>,
+    bytes: Vec<u8>,
+    /// Value is a blob, represented in JSON as base64 string.
+    blob: bool,
+}
+
+impl Chunks {
+    /// Starts reassembling a value at `path`, continuing a string already stored there.
+    fn resume(root: &mut serde_json::Value, path: &[u8]) -> Result<Self, Error> {
+        let bytes = match touch(root, &Path::from_slice(path))? {
+            serde_json::Value::String(str) => std::mem::take(str).into_bytes(),
+            _ => Vec::new(),
+        };
+        Ok(Chunks {
+            path: path.to_vec(),
+            bytes,
+            blob: false,
+        })
+    }
+
+    fn append(&mut self, offset: u64, chunk: &[u8]) -> Result<(), Error> {
+        let len = self.bytes.len() as u64;
+        match offset.cmp(&len) {
+            Ordering::Less => {
+                self.bytes.truncate(offset as usize);
+                self.bytes.extend_from_slice(chunk);
             }
+            Ordering::Equal => self.bytes.extend_from_slice(chunk),
+            Ordering::Greater => return Err(DecodeError::UnexpectedOffset { offset, len }.into()),
         }
+        Ok(())
     }
-    if !cont {
-        offset = 0;
+
+    fn finish(self, root: &mut serde_json::Value) -> Result<(), Error> {
+        let value = if self.blob {
+            simple_base64::encode(&self.bytes)
+        } else {
+            String::from_utf8(self.bytes).map_err(|e| DecodeError::InvalidUtf8(e.utf8_error()))?
+        };
+        *touch(root, &Path::from_slice(&self.path))? = serde_json::Value::String(value);
+        Ok(())
     }
-    (current, offset)
 }
 
 pub trait Merge: Sized {
     type Value: Default;
 
-    fn merge_into(self, acc: &mut Self::Value);
+    /// Merges entries into `acc`. Fails if any of the entries has a malformed path or value.
+    fn try_merge_into(self, acc: &mut Self::Value) -> Result<(), Error>;
+
+    /// Merges entries into `acc`.
+    ///
+    /// # Panics
+    ///
+    /// If any of the entries has a malformed path or value. Use [Merge::try_merge_into] for
+    /// entries coming from untrusted sources.
+    fn merge_into(self, acc: &mut Self::Value) {
+        if let Err(e) = self.try_merge_into(acc) {
+            panic!("cannot merge malformed entries: {e}");
+        }
+    }
+
+    fn try_merge(self) -> Result<Self::Value, Error> {
+        let mut acc = Self::Value::default();
+        self.try_merge_into(&mut acc)?;
+        Ok(acc)
+    }
 
     fn merge(self) -> Self::Value {
         let mut acc = Self::Value::default();
@@ -60,72 +125,63 @@ where
 {
     type Value = serde_json::Value;
 
-    fn merge_into(self, acc: &mut Self::Value) {
+    fn try_merge_into(self, acc: &mut Self::Value) -> Result<(), Error> {
+        let mut chunks: Option<Chunks> = None;
         for (path, value) in self {
-            let (target, offset) = touch(acc, &path);
-            if offset > 0 {
-                if let serde_json::Value::String(str) = target {
-                    let string_value = str::from_utf8(&value).unwrap();
-                    match offset.cmp(&string_value.len()) {
-                        Ordering::Less => str.replace_range(offset.., string_value),
-                        Ordering::Equal => str.push_str(string_value),
-                        Ordering::Greater => panic!(
-                            "cannot merge string at offset {} of string which len is {}",
-                            offset,
-                            str.len()
-                        ),
-                    }
+            if let Some((path_len, offset)) = path.split_chunk() {
+                let value_path = &path.as_bytes()[..path_len];
+                if chunks.as_ref().is_some_and(|c| c.path != value_path) {
+                    chunks.take().unwrap().finish(acc)?;
                 }
+                let chunks = match &mut chunks {
+                    Some(chunks) => chunks,
+                    None => chunks.insert(Chunks::resume(acc, value_path)?),
+                };
+                chunks.append(offset, &value)?;
                 continue;
             }
-            let tag = value[0];
-            match tag {
-                super::TAG_NULL => {
-                    *target = serde_json::Value::Null;
-                }
-                super::TAG_STRING => {
-                    let bytes = &value[1..];
-                    let string_value = String::from_utf8_lossy(bytes).to_string();
-                    *target = serde_json::Value::String(string_value);
-                    continue;
-                }
-                super::TAG_FLOAT => {
-                    let number: f64 = f64::from_be_bytes(value[1..].try_into().unwrap());
-                    *target = number.into();
-                }
-                super::TAG_BOOL_TRUE => {
-                    *target = serde_json::Value::Bool(true);
-                }
-                super::TAG_BOOL_FALSE => {
-                    *target = serde_json::Value::Bool(false);
-                }
-                tag => {
-                    let len = (tag & 0b0000_1111) as usize;
-                    let bytes = &value[1..1 + len];
-                    let mut zigzag: u64 = 0;
-                    for byte in bytes.iter().rev() {
-                        zigzag = (zigzag << 8) | *byte as u64;
-                    }
-                    let number = if zigzag & 1 == 0 {
-                        (zigzag >> 1) as i64
-                    } else {
-                        !((zigzag >> 1) as i64)
-                    };
-                    *target = number.into();
-                }
-                _ => {
-                    // Handle unknown tags or unsupported types
-                    continue;
-                }
+            if let Some(chunks) = chunks.take() {
+                chunks.finish(acc)?;
+            }
+            if value.first() == Some(&TAG_BYTES) {
+                // bytes value may be followed by continuation entries, see `write_blob`
+                chunks = Some(Chunks {
+                    path: path.as_bytes().to_vec(),
+                    bytes: value[1..].to_vec(),
+                    blob: true,
+                });
+                continue;
             }
+
+            let target = touch(acc, &path)?;
+            *target = match value.first() {
+                Some(&super::TAG_NULL) => serde_json::Value::Null,
+                Some(&super::TAG_STRING) => serde_json::Value::String(decode_str(&value)?.into()),
+                Some(&super::TAG_BOOL_TRUE) => serde_json::Value::Bool(true),
+                Some(&super::TAG_BOOL_FALSE) => serde_json::Value::Bool(false),
+                Some(&super::TAG_EMPTY_OBJECT) => serde_json::Value::Object(serde_json::Map::new()),
+                Some(&super::TAG_EMPTY_ARRAY) => serde_json::Value::Array(Vec::new()),
+                _ => match decode_number(&value)? {
+                    Some(number) => serde_json::Value::Number(number),
+                    // decimals out of the range of f64 are kept as text rather than lost
+                    None if value[0] == super::TAG_DECIMAL => {
+                        serde_json::Value::String(decode_decimal(&value)?.into())
+                    }
+                    None => serde_json::Value::Null,
+                },
+            };
+        }
+        if let Some(chunks) = chunks {
+            chunks.finish(acc)?;
         }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::JsonPath;
-    use crate::json::{Flatten, Merge};
+    use crate::json::{DecodeError, Flatten, Merge, ScalarRef, Value};
+    use crate::{Error, JsonPath, Path, PathBuf, PathError, PathSegment};
     use serde_json::json;
 
     #[test]
@@ -134,12 +190,178 @@ mod test {
         let actual = expected
             .clone()
             .flatten(100)
-            .into_iter()
             .map(|(path, value)| (path.into_path(), value))
             .merge();
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn flatten_merge_empty_containers() {
+        let samples = [
+            json!({}),
+            json!([]),
+            json!({ "tags": [], "meta": {} }),
+            json!({ "a": [[], {}, [[]], [{ "b": {} }]], "c": { "d": { "e": [] } } }),
+            json!([{}, [], null, [{}]]),
+        ];
+        for expected in samples {
+            let actual = expected
+                .clone()
+                .flatten(100)
+                .map(|(path, value)| (path.into_path(), value))
+                .merge();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn flatten_merge_numbers() {
+        let expected = json!({
+            "int": [0, 1, -1, 255, -256, i64::MAX, i64::MIN],
+            "uint": [i64::MAX as u64 + 1, u64::MAX],
+            "float": [0.5, -1.25, 1e300, f64::MIN_POSITIVE],
+        });
+        let actual = expected
+            .clone()
+            .flatten(100)
+            .map(|(path, value)| (path.into_path(), value))
+            .merge();
+        assert_eq!(actual, expected);
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn flatten_merge_decimals() {
+        let json = r#"{"big":123456789012345678901234567890,"price":1.10,"tiny":1e-400}"#;
+        let value: serde_json::Value = serde_json::from_str(json).unwrap();
+        let actual = value
+            .flatten(100)
+            .map(|(path, value)| (path.into_path(), value))
+            .merge();
+        assert_eq!(actual.to_string(), json);
+    }
+
+    #[test]
+    fn flatten_merge_chunked_strings() {
+        let expected = json!({
+            "a": "lorem ipsum dolor sit amet ".repeat(10),
+            "b": ["consectetur adipiscing elit ".repeat(5), "short"],
+        });
+        let actual = expected
+            .clone()
+            .flatten(40)
+            .map(|(path, value)| (path.into_path(), value))
+            .merge();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn flatten_merge_multibyte_strings() {
+        let expected: serde_json::Value =
+            serde_json::from_str(include_str!("../../assets/complex.json")).unwrap();
+        let expected = json!({ "doc": expected, "emoji": "😀🎉✨".repeat(30) });
+        for chunk_size in [24, 25, 26, 27, 64] {
+            let actual = expected
+                .clone()
+                .flatten(chunk_size)
+                .map(|(path, value)| (path.into_path(), value))
+                .merge();
+            assert_eq!(actual, expected, "chunk size {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn merge_blobs() {
+        let large: Vec<u8> = (0..150_000u32).map(|i| (i % 253) as u8).collect();
+        let mut buf = Vec::new();
+        let mut encoder = crate::PrefixEncoder::new(&mut buf);
+        encoder.write_blob(&[0, b'a'], large.as_slice()).unwrap();
+        encoder.write_blob(&[0, b'b'], &b"hello"[..]).unwrap();
+        let mut decoder = crate::PrefixDecoder::new(buf.as_slice());
+        let mut entries = Vec::new();
+        while let Some((path, value)) = decoder.read_next().unwrap() {
+            entries.push((path.to_owned(), smallvec::SmallVec::from_slice(value)));
+        }
+        let actual = entries.into_iter().merge();
+        let expected = json!({ "a": simple_base64::encode(&large), "b": "aGVsbG8=" });
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn merge_bytes() {
+        let value = ScalarRef::Bytes(b"hello").encode();
+        let actual = [(Path::from_slice(&[0, b'a']), value)].into_iter().merge();
+        assert_eq!(actual, json!({ "a": "aGVsbG8=" }));
+    }
+
+    #[test]
+    fn merge_decimal_out_of_range() {
+        let value = ScalarRef::Decimal("1e400").encode();
+        let actual = [(Path::from_slice(&[0, b'a']), value)].into_iter().merge();
+        if cfg!(feature = "arbitrary_precision") {
+            assert_eq!(actual.to_string(), r#"{"a":1e400}"#);
+        } else {
+            assert_eq!(actual, json!({ "a": "1e400" }));
+        }
+    }
+
+    #[test]
+    fn try_merge_malformed() {
+        let string = ScalarRef::Str("abc").encode();
+        let bad_path = [(Path::from_slice(&[0xFF]), string.clone())];
+        assert!(matches!(
+            bad_path.into_iter().try_merge(),
+            Err(Error::InvalidPath(_))
+        ));
+        let empty_value = [(Path::from_slice(&[0, b'a']), Value::new())];
+        assert!(matches!(
+            empty_value.into_iter().try_merge(),
+            Err(Error::InvalidValue(DecodeError::Empty))
+        ));
+        let chunk = |offset: u64| {
+            PathBuf::from_iter([PathSegment::Key("a"), offset.into(), PathSegment::Cont])
+                .into_path()
+        };
+        let gap = [
+            (chunk(0), Value::from_slice(b"ab")),
+            (chunk(5), Value::from_slice(b"cd")),
+        ];
+        assert!(matches!(
+            gap.into_iter().try_merge(),
+            Err(Error::InvalidValue(DecodeError::UnexpectedOffset {
+                offset: 5,
+                len: 2
+            }))
+        ));
+        // 7-byte array index, which would need ~2^44 nulls to fill the gap
+        let huge_index = [(
+            Path::from_slice(&[0, b'a', 0x17, 0x00, 0x0f, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            string.clone(),
+        )];
+        assert!(matches!(
+            huge_index.into_iter().try_merge(),
+            Err(Error::InvalidPath(PathError::IndexGapTooLong(_)))
+        ));
+        let invalid_utf8 = [(
+            Path::from_slice(&[0, b'a']),
+            Value::from_slice(&[crate::json::TAG_STRING, 0xff]),
+        )];
+        assert!(matches!(
+            invalid_utf8.into_iter().try_merge(),
+            Err(Error::InvalidValue(DecodeError::InvalidUtf8(_)))
+        ));
+        let split_char = [
+            (chunk(0), Value::from_slice(b"ab\xc3")),
+            (chunk(3), Value::from_slice(b"x")),
+        ];
+        assert!(matches!(
+            split_char.into_iter().try_merge(),
+            Err(Error::InvalidValue(DecodeError::InvalidUtf8(_)))
+        ));
+        let ok = [(Path::from_slice(&[0, b'a']), string)];
+        assert_eq!(ok.into_iter().try_merge().unwrap(), json!({ "a": "abc" }));
+    }
+
     #[test]
     fn flatten_filter_merge() {
         let json_path = JsonPath::parse("users[*].name").unwrap();
@@ -147,7 +369,6 @@ mod test {
         let actual = source
             .clone()
             .flatten(100)
-            .into_iter()
             .map(|(path, value)| (path.into_path(), value))
             .filter(|(path, _)| json_path.is_match(path))
             .merge();
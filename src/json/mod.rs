@@ -1,14 +1,31 @@
+mod decimal;
 mod flatten;
 mod merge;
+mod reader;
+pub mod scalar;
+mod writer;
 
-pub use flatten::Flatten;
+pub(crate) use flatten::flatten_str;
+pub use flatten::{Flatten, FlattenIter};
 pub use merge::Merge;
+pub use reader::{FlattenReader, ReadError, flatten_reader};
+pub use scalar::{DecodeError, ScalarRef};
+pub use writer::write_json;
 
 pub type Value = smallvec::SmallVec<u8, 10>;
 
+/// Maximum number of consecutive missing array elements filled with `null`, when an array
+/// is rebuilt from entries which skip some of its indexes.
+pub const MAX_ARRAY_GAP: u64 = u16::MAX as u64;
+
 pub(crate) const TAG_BOOL_FALSE: u8 = 0b1000_0000;
 pub(crate) const TAG_BOOL_TRUE: u8 = 0b1000_0001;
 pub(crate) const TAG_STRING: u8 = 0b1000_0010;
 pub(crate) const TAG_FLOAT: u8 = 0b1000_0011;
 pub(crate) const TAG_NULL: u8 = 0b1000_0100;
+pub(crate) const TAG_EMPTY_OBJECT: u8 = 0b1000_0101;
+pub(crate) const TAG_EMPTY_ARRAY: u8 = 0b1000_0110;
+pub(crate) const TAG_BYTES: u8 = 0b1000_0111;
+pub(crate) const TAG_DECIMAL: u8 = 0b1000_1000;
 pub(crate) const TAG_INTEGER: u8 = 0b0000_0000;
+pub(crate) const TAG_UINT: u8 = 0b0001_0000;
@@ -0,0 +1,574 @@
+use crate::PathBuf;
+use crate::json::scalar::{encode_bool, encode_null, encode_number, encode_str};
+use crate::json::{TAG_EMPTY_ARRAY, TAG_EMPTY_OBJECT};
+use smallvec::{SmallVec, smallvec};
+use std::io::Read;
+use std::str::FromStr;
+
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+type Entry = (PathBuf<Vec<u8>>, super::Value);
+
+/// Flattens JSON text read from `reader` into PEON entries, without parsing the whole document
+/// into `serde_json::Value` first.
+///
+/// Entries are produced lazily, in the order in which they appear in the input, so the memory
+/// used is proportional to the depth of the document and `chunk_size`: strings longer than that
+/// are emitted chunk by chunk, as soon as each chunk has been read.
+/// Since `serde_json::Value` keeps object keys sorted, output is the same as the one of
+/// [super::Flatten] only if object keys in the input are sorted as well.
+pub fn flatten_reader<R: Read>(reader: R, chunk_size: usize) -> FlattenReader<R> {
+    FlattenReader {
+        reader,
+        buf: vec![0; READ_BUFFER_SIZE].into_boxed_slice(),
+        pos: 0,
+        len: 0,
+        offset: 0,
+        chunk_size,
+        path: PathBuf::new(Vec::new()),
+        stack: Vec::new(),
+        scratch: Vec::new(),
+        string_start: 0,
+        expect: Expect::Value,
+    }
+}
+
+/// Iterator returned by [flatten_reader].
+pub struct FlattenReader<R> {
+    reader: R,
+    buf: Box<[u8]>,
+    pos: usize,
+    len: usize,
+    /// Number of bytes consumed from the reader before the current buffer.
+    offset: u64,
+    chunk_size: usize,
+    path: PathBuf<Vec<u8>>,
+    stack: Vec<Frame>,
+    /// Buffer used for unescaping strings and reading numbers. For long strings it holds the
+    /// unescaped bytes that have been read, but not emitted yet.
+    scratch: Vec<u8>,
+    /// Offset of the string value which is currently read.
+    string_start: u64,
+    expect: Expect,
+}
+
+/// Open JSON object or array.
+#[derive(Debug)]
+enum Frame {
+    Object { path_len: usize },
+    Array { path_len: usize, index: u64 },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Expect {
+    /// Any JSON value.
+    Value,
+    /// First member of the object or its closing bracket.
+    FirstMember,
+    /// Next member of the object.
+    Member,
+    /// First element of the array or its closing bracket.
+    FirstElement,
+    /// Separator or closing bracket of the current object/array.
+    Separator,
+    /// Next chunk of a long string value, starting at `offset` byte of the unescaped string.
+    /// `closed` is set once the closing quote has been read.
+    Chunk { offset: u64, closed: bool },
+    /// Nothing else - neither more input, nor entries.
+    Done,
+}
+
+impl<R: Read> FlattenReader<R> {
+    fn peek(&mut self) -> Result<Option<u8>, ReadError> {
+        if self.pos == self.len {
+            self.offset += self.len as u64;
+            self.pos = 0;
+            self.len = loop {
+                match self.reader.read(&mut self.buf) {
+                    Ok(n) => break n,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            };
+            if self.len == 0 {
+                return Ok(None);
+            }
+        }
+        Ok(Some(self.buf[self.pos]))
+    }
+
+    fn next_byte(&mut self) -> Result<u8, ReadError> {
+        match self.peek()? {
+            Some(byte) => {
+                self.pos += 1;
+                Ok(byte)
+            }
+            None => Err(ReadError::Eof),
+        }
+    }
+
+    fn byte_offset(&self) -> u64 {
+        self.offset + self.pos as u64
+    }
+
+    fn unexpected(&self, byte: u8) -> ReadError {
+        ReadError::UnexpectedByte {
+            byte,
+            offset: self.byte_offset() - 1,
+        }
+    }
+
+    /// Skips whitespaces and returns the next byte without consuming it.
+    fn peek_token(&mut self) -> Result<Option<u8>, ReadError> {
+        while let Some(byte) = self.peek()? {
+            if !matches!(byte, b' ' | b'\n' | b'\r' | b'\t') {
+                return Ok(Some(byte));
+            }
+            self.pos += 1;
+        }
+        Ok(None)
+    }
+
+    /// Skips whitespaces and consumes the next byte.
+    fn next_token(&mut self) -> Result<u8, ReadError> {
+        match self.peek_token()? {
+            Some(byte) => {
+                self.pos += 1;
+                Ok(byte)
+            }
+            None => Err(ReadError::Eof),
+        }
+    }
+
+    fn expect_literal(&mut self, literal: &[u8]) -> Result<(), ReadError> {
+        for expected in literal {
+            let byte = self.next_byte()?;
+            if byte != *expected {
+                return Err(self.unexpected(byte));
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the contents of a JSON string into the scratch buffer. Opening quote must have
+    /// been consumed already.
+    fn read_string(&mut self) -> Result<&str, ReadError> {
+        let start = self.byte_offset();
+        self.scratch.clear();
+        while self.read_string_char()? {}
+        std::str::from_utf8(&self.scratch).map_err(|_| ReadError::InvalidUtf8(start))
+    }
+
+    /// Reads string characters into the scratch buffer until it holds at least `min` bytes.
+    /// Returns `true` if the closing quote has been reached first.
+    fn fill_string(&mut self, min: usize) -> Result<bool, ReadError> {
+        while self.scratch.len() < min {
+            if !self.read_string_char()? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Reads a single (possibly escaped) string character into the scratch buffer. Returns
+    /// `false` if the closing quote was read instead.
+    fn read_string_char(&mut self) -> Result<bool, ReadError> {
+        match self.next_byte()? {
+            b'"' => return Ok(false),
+            b'\\' => {
+                let escaped = match self.next_byte()? {
+                    b'"' => '"',
+                    b'\\' => '\\',
+                    b'/' => '/',
+                    b'b' => '\x08',
+                    b'f' => '\x0c',
+                    b'n' => '\n',
+                    b'r' => '\r',
+                    b't' => '\t',
+                    b'u' => self.read_unicode_escape()?,
+                    _ => return Err(ReadError::InvalidEscape(self.byte_offset() - 2)),
+                };
+                let mut buf = [0u8; 4];
+                let encoded = escaped.encode_utf8(&mut buf);
+                self.scratch.extend_from_slice(encoded.as_bytes());
+            }
+            byte if byte < 0x20 => return Err(self.unexpected(byte)),
+            byte => self.scratch.push(byte),
+        }
+        Ok(true)
+    }
+
+    fn read_hex4(&mut self) -> Result<u16, ReadError> {
+        let mut value = 0u16;
+        for _ in 0..4 {
+            let byte = self.next_byte()?;
+            let digit = match byte {
+                b'0'..=b'9' => byte - b'0',
+                b'a'..=b'f' => byte - b'a' + 10,
+                b'A'..=b'F' => byte - b'A' + 10,
+                _ => return Err(ReadError::InvalidEscape(self.byte_offset() - 1)),
+            };
+            value = (value << 4) | digit as u16;
+        }
+        Ok(value)
+    }
+
+    fn read_unicode_escape(&mut self) -> Result<char, ReadError> {
+        let offset = self.byte_offset() - 2;
+        let high = self.read_hex4()?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                // surrogate pair must be followed by its low half
+                self.expect_literal(b"\\u")
+                    .map_err(|_| ReadError::InvalidEscape(offset))?;
+                let low = self.read_hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(ReadError::InvalidEscape(offset));
+                }
+                0x10000 + (((high as u32 - 0xD800) << 10) | (low as u32 - 0xDC00))
+            }
+            code => code as u32,
+        };
+        char::from_u32(code).ok_or(ReadError::InvalidEscape(offset))
+    }
+
+    fn read_number(&mut self, first: u8) -> Result<super::Value, ReadError> {
+        let start = self.byte_offset() - 1;
+        self.scratch.clear();
+        self.scratch.push(first);
+        while let Some(byte) = self.peek()? {
+            if !matches!(byte, b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E') {
+                break;
+            }
+            self.scratch.push(byte);
+            self.pos += 1;
+        }
+        let number = std::str::from_utf8(&self.scratch)
+            .ok()
+            .and_then(|str| serde_json::Number::from_str(str).ok())
+            .ok_or(ReadError::InvalidNumber(start))?;
+        Ok(encode_number(&number))
+    }
+
+    /// Reads the next JSON value at the current path. Returns an entry if the value was
+    /// a scalar, or `None` if it was a beginning of an object or array.
+    fn read_value(&mut self) -> Result<Option<Entry>, ReadError> {
+        let path_len = self.path.as_bytes().len();
+        let value = match self.next_token()? {
+            b'{' => {
+                self.stack.push(Frame::Object { path_len });
+                self.expect = Expect::FirstMember;
+                return Ok(None);
+            }
+            b'[' => {
+                self.stack.push(Frame::Array { path_len, index: 0 });
+                self.expect = Expect::FirstElement;
+                return Ok(None);
+            }
+            b'"' => {
+                self.string_start = self.byte_offset();
+                self.scratch.clear();
+                // read one byte past the chunk size to find out if the string must be split
+                let closed = self.fill_string(self.chunk_size + 1)?;
+                if !closed || self.scratch.len() > self.chunk_size {
+                    self.expect = Expect::Chunk { offset: 0, closed };
+                    return self.read_chunk().map(Some);
+                }
+                let value = std::str::from_utf8(&self.scratch)
+                    .map_err(|_| ReadError::InvalidUtf8(self.string_start))?;
+                encode_str(value)
+            }
+            b't' => {
+                self.expect_literal(b"rue")?;
+                encode_bool(true)
+            }
+            b'f' => {
+                self.expect_literal(b"alse")?;
+                encode_bool(false)
+            }
+            b'n' => {
+                self.expect_literal(b"ull")?;
+                encode_null()
+            }
+            byte @ (b'-' | b'0'..=b'9') => self.read_number(byte)?,
+            byte => return Err(self.unexpected(byte)),
+        };
+        self.expect = Expect::Separator;
+        Ok(Some((self.path.clone(), value)))
+    }
+
+    /// Emits the next chunk of a long string value, reading only as much of the input as the
+    /// chunk needs. Chunks are split the same way as
+    /// [crate::json::flatten::flatten_str] does it.
+    fn read_chunk(&mut self) -> Result<Entry, ReadError> {
+        let Expect::Chunk { offset, mut closed } = self.expect else {
+            unreachable!("read_chunk called outside of a string value");
+        };
+        let base_len = self.path.as_bytes().len();
+        self.path.push_index(offset).unwrap();
+        self.path.push_continued().unwrap();
+        let budget = self
+            .chunk_size
+            .saturating_sub(self.path.as_bytes().len() + 6);
+        if !closed {
+            // one byte past the chunk tells if it ends at a character boundary and whether the
+            // string goes on; the first (up to 4 bytes) character is emitted whole even if it
+            // doesn't fit into the budget
+            closed = self.fill_string((budget + 1).max(5))?;
+        }
+        let len = self.scratch.len();
+        let mut end = budget.min(len);
+        while end > 0 && end < len && is_utf8_continuation(self.scratch[end]) {
+            end -= 1;
+        }
+        if end == 0 {
+            end = utf8_width(self.scratch[0]).clamp(1, len);
+        }
+        let chunk = &self.scratch[..end];
+        if std::str::from_utf8(chunk).is_err() {
+            self.path.truncate(base_len);
+            return Err(ReadError::InvalidUtf8(self.string_start));
+        }
+        let entry = (self.path.clone(), SmallVec::from_slice(chunk));
+        self.path.truncate(base_len);
+        self.scratch.drain(..end);
+        self.expect = if closed && self.scratch.is_empty() {
+            Expect::Separator
+        } else {
+            Expect::Chunk {
+                offset: offset + end as u64,
+                closed,
+            }
+        };
+        Ok(entry)
+    }
+
+    /// Reads the key of the next object member and pushes it onto the current path.
+    fn read_member(&mut self, path_len: usize) -> Result<(), ReadError> {
+        match self.next_token()? {
+            b'"' => {}
+            byte => return Err(self.unexpected(byte)),
+        }
+        let mut path = std::mem::replace(&mut self.path, PathBuf::new(Vec::new()));
+        path.truncate(path_len);
+        let result = self.read_string().map(|key| path.push_key(key).unwrap());
+        self.path = path;
+        result?;
+        match self.next_token()? {
+            b':' => {
+                self.expect = Expect::Value;
+                Ok(())
+            }
+            byte => Err(self.unexpected(byte)),
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(Frame::Object { path_len } | Frame::Array { path_len, .. }) = self.stack.pop() {
+            self.path.truncate(path_len);
+        }
+        self.expect = Expect::Separator;
+    }
+
+    fn read_next(&mut self) -> Result<Option<Entry>, ReadError> {
+        loop {
+            match self.expect {
+                Expect::Done => return Ok(None),
+                Expect::Chunk { .. } => return self.read_chunk().map(Some),
+                Expect::Value => {
+                    if let Some(entry) = self.read_value()? {
+                        return Ok(Some(entry));
+                    }
+                }
+                Expect::FirstMember => {
+                    if self.peek_token()? == Some(b'}') {
+                        self.pos += 1;
+                        let entry = (self.path.clone(), smallvec![TAG_EMPTY_OBJECT]);
+                        self.close();
+                        return Ok(Some(entry));
+                    } else if let Some(Frame::Object { path_len }) = self.stack.last() {
+                        self.read_member(*path_len)?;
+                    }
+                }
+                Expect::Member => {
+                    if let Some(Frame::Object { path_len }) = self.stack.last() {
+                        self.read_member(*path_len)?;
+                    }
+                }
+                Expect::FirstElement => {
+                    if self.peek_token()? == Some(b']') {
+                        self.pos += 1;
+                        let entry = (self.path.clone(), smallvec![TAG_EMPTY_ARRAY]);
+                        self.close();
+                        return Ok(Some(entry));
+                    } else {
+                        self.path.push_index(0).unwrap();
+                        self.expect = Expect::Value;
+                    }
+                }
+                Expect::Separator => match self.stack.last_mut() {
+                    None => {
+                        // root value is complete, only whitespaces may follow
+                        return match self.peek_token()? {
+                            None => {
+                                self.expect = Expect::Done;
+                                Ok(None)
+                            }
+                            Some(byte) => {
+                                self.pos += 1;
+                                Err(self.unexpected(byte))
+                            }
+                        };
+                    }
+                    Some(Frame::Object { .. }) => match self.next_token()? {
+                        b',' => self.expect = Expect::Member,
+                        b'}' => self.close(),
+                        byte => return Err(self.unexpected(byte)),
+                    },
+                    Some(Frame::Array { path_len, index }) => {
+                        let path_len = *path_len;
+                        *index += 1;
+                        let index = *index;
+                        match self.next_token()? {
+                            b',' => {
+                                self.path.truncate(path_len);
+                                self.path.push_index(index).unwrap();
+                                self.expect = Expect::Value;
+                            }
+                            b']' => self.close(),
+                            byte => return Err(self.unexpected(byte)),
+                        }
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for FlattenReader<R> {
+    type Item = Result<Entry, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read_next() {
+            Ok(entry) => entry.map(Ok),
+            Err(e) => {
+                // input is malformed, there's no way to recover
+                self.expect = Expect::Done;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0b1100_0000 == 0b1000_0000
+}
+
+/// Length of the UTF-8 character starting with `byte`, or 0 if it's not a valid first byte.
+fn utf8_width(byte: u8) -> usize {
+    match byte {
+        0x00..=0x7F => 1,
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => 0,
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReadError {
+    #[error("failed to read JSON input: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("unexpected end of JSON input")]
+    Eof,
+    #[error("unexpected character {:?} at offset {offset}", char::from(*byte))]
+    UnexpectedByte { byte: u8, offset: u64 },
+    #[error("invalid number at offset {0}")]
+    InvalidNumber(u64),
+    #[error("invalid string escape sequence at offset {0}")]
+    InvalidEscape(u64),
+    #[error("invalid utf-8 string at offset {0}")]
+    InvalidUtf8(u64),
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json::{Flatten, ReadError, flatten_reader};
+    use serde_json::json;
+
+    fn assert_same_as_flatten(json: &str, chunk_size: usize) {
+        let value: serde_json::Value = serde_json::from_str(json).unwrap();
+        let expected: Vec<_> = value.flatten(chunk_size).collect();
+        let actual: Vec<_> = flatten_reader(json.as_bytes(), chunk_size)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn flatten_reader_complex() {
+        let value: serde_json::Value =
+            serde_json::from_str(include_str!("../../assets/complex.json")).unwrap();
+        // serde_json::Value keeps keys in sorted order
+        let sorted = serde_json::to_string_pretty(&value).unwrap();
+        assert_same_as_flatten(&sorted, u16::MAX as usize);
+        assert_same_as_flatten(&sorted, 100);
+    }
+
+    #[test]
+    fn flatten_reader_scalars() {
+        let json = json!({
+            "a": [1, -2, 3.5, -1e-7, 18446744073709551615u64, true, false, null],
+            "b": { "c": [[], {}, [[1]]] },
+            "d": "escaped \"quotes\" \\ \n \u{1F600} \u{e9}"
+        })
+        .to_string();
+        assert_same_as_flatten(&json, 100);
+        assert_same_as_flatten(r#""😀 é""#, 100);
+        assert_same_as_flatten(" 42 ", 100);
+    }
+
+    #[test]
+    fn flatten_reader_long_strings() {
+        let json = json!({
+            "a": "żółć \"gęślą\" jaźń 😀 ".repeat(20),
+            "b": ["x".repeat(300), "😀".repeat(50), "\u{e9}\n".repeat(40)],
+        })
+        .to_string();
+        for chunk_size in [0, 1, 5, 13, 20, 64, 299, 300, 301] {
+            assert_same_as_flatten(&json, chunk_size);
+        }
+    }
+
+    #[test]
+    fn flatten_reader_streams_string_chunks() {
+        // endless string: chunks must be emitted before its end is reached
+        let input = std::io::Read::chain(&b"\""[..], std::io::repeat(b'a'));
+        let chunks: Vec<_> = flatten_reader(input, 64)
+            .take(1000)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(chunks.len(), 1000);
+
+        let res: Result<Vec<_>, ReadError> =
+            flatten_reader(&b"\"abc\xff\xfe def\""[..], 5).collect();
+        assert!(matches!(res, Err(ReadError::InvalidUtf8(1))));
+    }
+
+    #[test]
+    fn flatten_reader_malformed() {
+        let cases = [
+            r#"{"a": 1"#,
+            r#"{"a" 1}"#,
+            r#"[1, 2,]"#,
+            r#"{"a": tru}"#,
+            r#""\ud83d""#,
+            r#"[1] 2"#,
+            r#"[1.2.3]"#,
+        ];
+        for json in cases {
+            let res: Result<Vec<_>, ReadError> = flatten_reader(json.as_bytes(), 100).collect();
+            assert!(res.is_err(), "expected `{json}` to fail");
+        }
+    }
+}
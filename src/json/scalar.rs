@@ -0,0 +1,592 @@
+//! Codec of scalar values stored in PEON entries.
+//!
+//! Every encoded value starts with a single tag byte describing its type, optionally followed
+//! by the payload:
+//!
+//! | tag                 | payload                                                         |
+//! |---------------------|-----------------------------------------------------------------|
+//! | `0b0000_LLLL`       | signed integer: `L` (0..=8) big-endian bytes of its zigzag form |
+//! | `0b0001_LLLL`       | unsigned integer above `i64::MAX`: `L` (0..=8) big-endian bytes |
+//! | `0b1000_0000`       | `false`                                                         |
+//! | `0b1000_0001`       | `true`                                                          |
+//! | `0b1000_0010`       | UTF-8 string bytes                                              |
+//! | `0b1000_0011`       | 64-bit IEEE 754 float, big-endian                               |
+//! | `0b1000_0100`       | `null`                                                          |
+//! | `0b1000_0101`       | empty object                                                    |
+//! | `0b1000_0110`       | empty array                                                     |
+//! | `0b1000_0111`       | raw bytes                                                       |
+//! | `0b1000_1000`       | decimal number as text, following the JSON number grammar       |
+//!
+//! Continuation entries (paths ending with `[offset]..`) carry raw chunks of a longer value
+//! without any tag byte.
+//!
+//! Integers use the smallest of 0, 1, 2, 4 or 8 bytes able to fit their zigzag form, so zero is
+//! encoded as a single tag byte. Unsigned integers which fit into `i64` are always encoded as
+//! signed ones, which keeps a single representation for every number. Decimals are used for
+//! numbers which can't be represented by any of these without loss of precision, which is only
+//! possible with `arbitrary_precision` feature enabled.
+
+use crate::json::decimal::Decimal;
+use crate::json::{
+    TAG_BOOL_FALSE, TAG_BOOL_TRUE, TAG_BYTES, TAG_DECIMAL, TAG_EMPTY_ARRAY, TAG_EMPTY_OBJECT,
+    TAG_FLOAT, TAG_INTEGER, TAG_NULL, TAG_STRING, TAG_UINT, Value,
+};
+use crate::{Path, PathSegment, size_hint};
+use smallvec::smallvec;
+use std::cmp::Ordering;
+
+const TAG_KIND_MASK: u8 = 0b1111_0000;
+const TAG_LEN_MASK: u8 = 0b0000_1111;
+
+pub fn encode_null() -> Value {
+    smallvec![TAG_NULL]
+}
+
+pub fn encode_bool(value: bool) -> Value {
+    smallvec![if value { TAG_BOOL_TRUE } else { TAG_BOOL_FALSE }]
+}
+
+pub fn decode_bool(value: &[u8]) -> Result<bool, DecodeError> {
+    match tag(value)? {
+        TAG_BOOL_TRUE if value.len() == 1 => Ok(true),
+        TAG_BOOL_FALSE if value.len() == 1 => Ok(false),
+        tag @ (TAG_BOOL_TRUE | TAG_BOOL_FALSE) => Err(DecodeError::InvalidLength {
+            tag,
+            len: value.len(),
+        }),
+        tag => Err(DecodeError::UnexpectedTag(tag)),
+    }
+}
+
+pub fn encode_integer(value: i64) -> Value {
+    let zigzag = ((value << 1) ^ (value >> 63)) as u64;
+    encode_varint(TAG_INTEGER, zigzag)
+}
+
+pub fn decode_integer(value: &[u8]) -> Result<i64, DecodeError> {
+    let zigzag = decode_varint(TAG_INTEGER, value)?;
+    Ok(((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64))
+}
+
+/// Encodes an unsigned integer. Values up to `i64::MAX` are encoded the same way as
+/// [encode_integer] does, so they can be decoded by either [decode_integer] or
+/// [decode_unsigned].
+pub fn encode_unsigned(value: u64) -> Value {
+    match i64::try_from(value) {
+        Ok(value) => encode_integer(value),
+        Err(_) => encode_varint(TAG_UINT, value),
+    }
+}
+
+pub fn decode_unsigned(value: &[u8]) -> Result<u64, DecodeError> {
+    match tag(value)? & TAG_KIND_MASK {
+        TAG_INTEGER => {
+            let tag = value[0];
+            u64::try_from(decode_integer(value)?).map_err(|_| DecodeError::UnexpectedTag(tag))
+        }
+        _ => decode_varint(TAG_UINT, value),
+    }
+}
+
+pub fn encode_float(value: f64) -> Value {
+    let mut buf = smallvec![TAG_FLOAT];
+    buf.extend_from_slice(&value.to_be_bytes());
+    buf
+}
+
+pub fn decode_float(value: &[u8]) -> Result<f64, DecodeError> {
+    match tag(value)? {
+        TAG_FLOAT => match value[1..].try_into() {
+            Ok(bytes) => Ok(f64::from_be_bytes(bytes)),
+            Err(_) => Err(DecodeError::InvalidLength {
+                tag: TAG_FLOAT,
+                len: value.len(),
+            }),
+        },
+        tag => Err(DecodeError::UnexpectedTag(tag)),
+    }
+}
+
+/// Encodes a decimal number. `value` is expected to follow the JSON number grammar.
+pub fn encode_decimal(value: &str) -> Value {
+    let mut buf = Value::with_capacity(value.len() + 1);
+    buf.push(TAG_DECIMAL);
+    buf.extend_from_slice(value.as_bytes());
+    buf
+}
+
+pub fn decode_decimal(value: &[u8]) -> Result<&str, DecodeError> {
+    match tag(value)? {
+        TAG_DECIMAL => match std::str::from_utf8(&value[1..]) {
+            Ok(text) if Decimal::parse(text).is_some() => Ok(text),
+            _ => Err(DecodeError::InvalidDecimal),
+        },
+        tag => Err(DecodeError::UnexpectedTag(tag)),
+    }
+}
+
+/// Encodes a JSON number, picking the signed, unsigned or float representation depending on
+/// which one `serde_json` holds.
+///
+/// With `arbitrary_precision` feature, numbers whose text doesn't survive a round trip through
+/// `f64` (i.e. `1.10` or `123456789012345678901234567890`) are encoded as decimals.
+pub fn encode_number(value: &serde_json::Number) -> Value {
+    if let Some(v) = value.as_i64() {
+        return encode_integer(v);
+    }
+    if let Some(v) = value.as_u64() {
+        return encode_unsigned(v);
+    }
+    #[cfg(feature = "arbitrary_precision")]
+    {
+        let text = value.as_str();
+        let float = text.parse().ok().and_then(serde_json::Number::from_f64);
+        if float.as_ref().map(serde_json::Number::as_str) != Some(text) {
+            return encode_decimal(text);
+        }
+    }
+    match value.as_f64() {
+        Some(v) => encode_float(v),
+        None => encode_decimal(&value.to_string()),
+    }
+}
+
+/// Decodes any of the numeric values into a JSON number. Non-finite floats, which have no JSON
+/// representation, are decoded as `None`.
+///
+/// Decimals are decoded exactly with `arbitrary_precision` feature. Otherwise they are rounded
+/// to the nearest `f64`, and become `None` when out of its range.
+pub fn decode_number(value: &[u8]) -> Result<Option<serde_json::Number>, DecodeError> {
+    match tag(value)? {
+        TAG_FLOAT => Ok(serde_json::Number::from_f64(decode_float(value)?)),
+        TAG_DECIMAL => Ok(decode_decimal(value)?.parse().ok()),
+        tag if tag & TAG_KIND_MASK == TAG_INTEGER => Ok(Some(decode_integer(value)?.into())),
+        tag if tag & TAG_KIND_MASK == TAG_UINT => Ok(Some(decode_unsigned(value)?.into())),
+        tag => Err(DecodeError::UnexpectedTag(tag)),
+    }
+}
+
+pub fn encode_str(value: &str) -> Value {
+    let bytes = value.as_bytes();
+    let mut buf = Value::with_capacity(bytes.len() + 1);
+    buf.push(TAG_STRING);
+    buf.extend_from_slice(bytes);
+    buf
+}
+
+pub fn decode_str(value: &[u8]) -> Result<&str, DecodeError> {
+    match tag(value)? {
+        TAG_STRING => Ok(std::str::from_utf8(&value[1..])?),
+        tag => Err(DecodeError::UnexpectedTag(tag)),
+    }
+}
+
+/// Scalar value decoded from a PEON entry, borrowing strings and bytes from the entry payload.
+///
+/// Numbers of different kinds are compared by their exact values, so they can be used in query
+/// filters regardless of how they were encoded, i.e. `Int(1) == Float(1.0)` and
+/// `Decimal("0.10000000000000000001") > Float(0.1)`. Floats are compared using their shortest
+/// decimal representation which round trips back to the same float. Values of different kinds
+/// (e.g. strings and numbers) are not comparable.
+#[derive(Debug, Clone, Copy)]
+pub enum ScalarRef<'a> {
+    Null,
+    Bool(bool),
+    /// Any integer that fits into `i64`.
+    Int(i64),
+    /// Unsigned integer above `i64::MAX`.
+    UInt(u64),
+    Float(f64),
+    /// Decimal number in its textual form, following the JSON number grammar.
+    Decimal(&'a str),
+    Str(&'a str),
+    Bytes(&'a [u8]),
+    EmptyObject,
+    EmptyArray,
+    /// Raw chunk of a string or bytes value, carried by a continuation entry.
+    Chunk(&'a [u8]),
+}
+
+impl<'a> ScalarRef<'a> {
+    /// Decodes a tagged entry value. Continuation entries carry no tag, so their values should
+    /// be decoded with [ScalarRef::from_entry] instead.
+    pub fn decode(value: &'a [u8]) -> Result<Self, DecodeError> {
+        let tag = tag(value)?;
+        let scalar = match tag {
+            TAG_NULL => ScalarRef::Null,
+            TAG_BOOL_TRUE | TAG_BOOL_FALSE => ScalarRef::Bool(decode_bool(value)?),
+            TAG_FLOAT => ScalarRef::Float(decode_float(value)?),
+            TAG_DECIMAL => ScalarRef::Decimal(decode_decimal(value)?),
+            TAG_STRING => ScalarRef::Str(decode_str(value)?),
+            TAG_BYTES => ScalarRef::Bytes(&value[1..]),
+            TAG_EMPTY_OBJECT => ScalarRef::EmptyObject,
+            TAG_EMPTY_ARRAY => ScalarRef::EmptyArray,
+            _ if tag & TAG_KIND_MASK == TAG_INTEGER => ScalarRef::Int(decode_integer(value)?),
+            _ if tag & TAG_KIND_MASK == TAG_UINT => match decode_unsigned(value)? {
+                value if value > i64::MAX as u64 => ScalarRef::UInt(value),
+                value => ScalarRef::Int(value as i64),
+            },
+            _ => return Err(DecodeError::UnexpectedTag(tag)),
+        };
+        match scalar {
+            ScalarRef::Null | ScalarRef::EmptyObject | ScalarRef::EmptyArray if value.len() > 1 => {
+                Err(DecodeError::InvalidLength {
+                    tag,
+                    len: value.len(),
+                })
+            }
+            scalar => Ok(scalar),
+        }
+    }
+
+    /// Decodes a value of the entry stored under `path`, recognizing continuation chunks.
+    pub fn from_entry(path: &Path, value: &'a [u8]) -> Result<Self, DecodeError> {
+        match path.iter().last() {
+            Some(Ok(PathSegment::Cont)) => Ok(ScalarRef::Chunk(value)),
+            _ => Self::decode(value),
+        }
+    }
+
+    /// Appends the encoded value to `buf`.
+    pub fn encode_into(&self, buf: &mut Value) {
+        match *self {
+            ScalarRef::Null => buf.push(TAG_NULL),
+            ScalarRef::Bool(value) => buf.extend_from_slice(&encode_bool(value)),
+            ScalarRef::Int(value) => buf.extend_from_slice(&encode_integer(value)),
+            ScalarRef::UInt(value) => buf.extend_from_slice(&encode_unsigned(value)),
+            ScalarRef::Float(value) => buf.extend_from_slice(&encode_float(value)),
+            ScalarRef::Decimal(value) => buf.extend_from_slice(&encode_decimal(value)),
+            ScalarRef::Str(value) => {
+                buf.push(TAG_STRING);
+                buf.extend_from_slice(value.as_bytes());
+            }
+            ScalarRef::Bytes(value) => {
+                buf.push(TAG_BYTES);
+                buf.extend_from_slice(value);
+            }
+            ScalarRef::EmptyObject => buf.push(TAG_EMPTY_OBJECT),
+            ScalarRef::EmptyArray => buf.push(TAG_EMPTY_ARRAY),
+            ScalarRef::Chunk(value) => buf.extend_from_slice(value),
+        }
+    }
+
+    pub fn encode(&self) -> Value {
+        let mut buf = Value::new();
+        self.encode_into(&mut buf);
+        buf
+    }
+
+    fn as_decimal(&self) -> Option<Decimal> {
+        match *self {
+            ScalarRef::Int(value) => Some(Decimal::from_i64(value)),
+            ScalarRef::UInt(value) => Some(Decimal::from_u64(value)),
+            ScalarRef::Float(value) => Decimal::from_f64(value),
+            ScalarRef::Decimal(value) => Decimal::parse(value),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> PartialEq for ScalarRef<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl<'a> PartialOrd for ScalarRef<'a> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (ScalarRef::Null, ScalarRef::Null)
+            | (ScalarRef::EmptyObject, ScalarRef::EmptyObject)
+            | (ScalarRef::EmptyArray, ScalarRef::EmptyArray) => Some(Ordering::Equal),
+            (ScalarRef::Bool(a), ScalarRef::Bool(b)) => a.partial_cmp(b),
+            (ScalarRef::Str(a), ScalarRef::Str(b)) => a.partial_cmp(b),
+            (ScalarRef::Bytes(a), ScalarRef::Bytes(b)) => a.partial_cmp(b),
+            (ScalarRef::Chunk(a), ScalarRef::Chunk(b)) => a.partial_cmp(b),
+            (ScalarRef::Int(a), ScalarRef::Int(b)) => a.partial_cmp(b),
+            (ScalarRef::Float(a), ScalarRef::Float(b)) => a.partial_cmp(b),
+            (a, b) => a.as_decimal()?.partial_cmp(&b.as_decimal()?),
+        }
+    }
+}
+
+fn tag(value: &[u8]) -> Result<u8, DecodeError> {
+    value.first().copied().ok_or(DecodeError::Empty)
+}
+
+fn encode_varint(kind: u8, value: u64) -> Value {
+    let byte_len = size_hint(value);
+    let mut buf = smallvec![kind | byte_len];
+    buf.extend_from_slice(&value.to_be_bytes()[(8 - byte_len as usize)..]);
+    buf
+}
+
+fn decode_varint(kind: u8, value: &[u8]) -> Result<u64, DecodeError> {
+    let tag = tag(value)?;
+    if tag & TAG_KIND_MASK != kind {
+        return Err(DecodeError::UnexpectedTag(tag));
+    }
+    let byte_len = (tag & TAG_LEN_MASK) as usize;
+    if byte_len > 8 || value.len() != byte_len + 1 {
+        return Err(DecodeError::InvalidLength {
+            tag,
+            len: value.len(),
+        });
+    }
+    let mut bytes = [0u8; 8];
+    bytes[(8 - byte_len)..].copy_from_slice(&value[1..]);
+    Ok(u64::from_be_bytes(bytes))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("value is empty")]
+    Empty,
+    #[error("unexpected value tag: {0:#010b}")]
+    UnexpectedTag(u8),
+    #[error("invalid length of value tagged {tag:#010b}: {len}")]
+    InvalidLength { tag: u8, len: usize },
+    #[error("invalid utf-8 string: {0}")]
+    InvalidUtf8(#[from] std::str::Utf8Error),
+    #[error("invalid decimal number")]
+    InvalidDecimal,
+    #[error("continuation at offset {offset} of value which length is {len}")]
+    UnexpectedOffset { offset: u64, len: u64 },
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn integer_layout() {
+        assert_eq!(encode_integer(0).as_slice(), &[TAG_INTEGER]);
+        assert_eq!(encode_integer(-1).as_slice(), &[TAG_INTEGER | 1, 1]);
+        assert_eq!(encode_integer(1).as_slice(), &[TAG_INTEGER | 1, 2]);
+        assert_eq!(encode_integer(-129).as_slice(), &[TAG_INTEGER | 2, 1, 1]);
+        assert_eq!(
+            encode_integer(i64::MIN).as_slice(),
+            &[
+                TAG_INTEGER | 8,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff,
+                0xff
+            ]
+        );
+        assert_eq!(encode_unsigned(1).as_slice(), &[TAG_INTEGER | 1, 2]);
+        assert_eq!(
+            encode_unsigned(u64::MAX).as_slice(),
+            &[TAG_UINT | 8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            encode_float(1.0).as_slice(),
+            &[TAG_FLOAT, 0x3f, 0xf0, 0, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn decode_malformed() {
+        assert!(matches!(decode_integer(&[]), Err(DecodeError::Empty)));
+        assert!(matches!(
+            decode_integer(&[TAG_INTEGER | 9, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(DecodeError::InvalidLength { .. })
+        ));
+        assert!(matches!(
+            decode_integer(&[TAG_INTEGER | 2, 1]),
+            Err(DecodeError::InvalidLength { .. })
+        ));
+        assert!(matches!(
+            decode_integer(&[TAG_FLOAT, 1]),
+            Err(DecodeError::UnexpectedTag(TAG_FLOAT))
+        ));
+        assert!(matches!(
+            decode_float(&[TAG_FLOAT, 1]),
+            Err(DecodeError::InvalidLength { .. })
+        ));
+        assert!(matches!(
+            decode_unsigned(&encode_integer(-1)),
+            Err(DecodeError::UnexpectedTag(_))
+        ));
+        assert!(matches!(
+            decode_str(&[TAG_STRING, 0xff]),
+            Err(DecodeError::InvalidUtf8(_))
+        ));
+        assert!(matches!(
+            decode_bool(&[TAG_BOOL_TRUE, 0]),
+            Err(DecodeError::InvalidLength { .. })
+        ));
+    }
+
+    #[test]
+    fn number_round_trip() {
+        for json in [
+            "0",
+            "-1",
+            "9223372036854775807",
+            "9223372036854775808",
+            "18446744073709551615",
+            "-9223372036854775808",
+            "0.5",
+            "-1e300",
+        ] {
+            let number: serde_json::Number = json.parse().unwrap();
+            let decoded = decode_number(&encode_number(&number)).unwrap();
+            assert_eq!(decoded, Some(number));
+        }
+        assert_eq!(decode_number(&encode_float(f64::NAN)).unwrap(), None);
+    }
+
+    #[test]
+    fn scalar_ref_round_trip() {
+        let scalars = [
+            ScalarRef::Null,
+            ScalarRef::Bool(true),
+            ScalarRef::Bool(false),
+            ScalarRef::Int(-42),
+            ScalarRef::UInt(u64::MAX),
+            ScalarRef::Float(0.25),
+            ScalarRef::Str("Alice"),
+            ScalarRef::Bytes(&[0, 1, 0xff]),
+            ScalarRef::EmptyObject,
+            ScalarRef::EmptyArray,
+        ];
+        for scalar in scalars {
+            assert_eq!(ScalarRef::decode(&scalar.encode()).unwrap(), scalar);
+        }
+        assert_eq!(
+            ScalarRef::decode(&encode_unsigned(7)).unwrap(),
+            ScalarRef::Int(7)
+        );
+        assert!(matches!(
+            ScalarRef::decode(&[TAG_NULL, 0]),
+            Err(DecodeError::InvalidLength { .. })
+        ));
+    }
+
+    #[test]
+    fn scalar_ref_from_filtered_entries() {
+        use crate::JsonPath;
+        use crate::json::Flatten;
+
+        let json_path = JsonPath::parse("users[*].*").unwrap();
+        let description = "lorem ipsum dolor sit amet ".repeat(4);
+        let json = serde_json::json!({
+            "users": [
+                { "age": 25, "name": "Alice" },
+                { "bio": description, "name": "Bob" }
+            ]
+        });
+        let mut chunks = Vec::new();
+        let mut scalars = Vec::new();
+        for (path, value) in json.flatten(50) {
+            let path = path.as_path();
+            if !json_path.is_match(&path) {
+                continue;
+            }
+            match ScalarRef::from_entry(&path, &value).unwrap() {
+                ScalarRef::Chunk(chunk) => chunks.extend_from_slice(chunk),
+                scalar => scalars.push(format!("{path} = {scalar:?}")),
+            }
+        }
+        assert_eq!(chunks, description.as_bytes());
+        assert_eq!(
+            scalars,
+            vec![
+                "$.users[0].age = Int(25)",
+                "$.users[0].name = Str(\"Alice\")",
+                "$.users[1].name = Str(\"Bob\")",
+            ]
+        );
+    }
+
+    #[test]
+    fn scalar_ref_numeric_ordering() {
+        let sorted = [
+            ScalarRef::Int(i64::MIN),
+            ScalarRef::Float(-1.5),
+            ScalarRef::Decimal("-1.4999999999999999999999"),
+            ScalarRef::Int(0),
+            ScalarRef::Float(0.1),
+            ScalarRef::Decimal("0.10000000000000000001"),
+            ScalarRef::Int(i64::MAX),
+            ScalarRef::UInt(i64::MAX as u64 + 1),
+            ScalarRef::Decimal("18446744073709551616"),
+            ScalarRef::Float(1e300),
+        ];
+        for pair in sorted.windows(2) {
+            assert!(pair[0] < pair[1], "{:?} < {:?}", pair[0], pair[1]);
+        }
+        assert_eq!(ScalarRef::Int(1), ScalarRef::Float(1.0));
+        assert_eq!(ScalarRef::Decimal("1.50"), ScalarRef::Float(1.5));
+        assert_eq!(ScalarRef::Str("1").partial_cmp(&ScalarRef::Int(1)), None);
+        assert_eq!(
+            ScalarRef::Float(f64::NAN).partial_cmp(&ScalarRef::Int(1)),
+            None
+        );
+    }
+
+    #[test]
+    fn decimal_round_trip() {
+        let encoded = encode_decimal("-1.10e-2");
+        assert_eq!(
+            ScalarRef::decode(&encoded).unwrap(),
+            ScalarRef::Decimal("-1.10e-2")
+        );
+        let number = decode_number(&encoded).unwrap().unwrap();
+        assert_eq!(number.as_f64(), Some(-0.011));
+        assert!(matches!(
+            decode_decimal(&encode_decimal("1.2.3")),
+            Err(DecodeError::InvalidDecimal)
+        ));
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn encode_number_arbitrary_precision() {
+        for (json, expected) in [
+            ("0.5", encode_float(0.5)),
+            ("1e300", encode_float(1e300)),
+            ("1.10", encode_decimal("1.10")),
+            ("1e400", encode_decimal("1e400")),
+            (
+                "123456789012345678901234567890",
+                encode_decimal("123456789012345678901234567890"),
+            ),
+        ] {
+            let number: serde_json::Number = json.parse().unwrap();
+            assert_eq!(encode_number(&number), expected, "{json}");
+            assert_eq!(decode_number(&expected).unwrap(), Some(number));
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn integer_round_trip(value: i64) {
+            prop_assert_eq!(decode_integer(&encode_integer(value)).unwrap(), value);
+        }
+
+        #[test]
+        fn unsigned_round_trip(value: u64) {
+            prop_assert_eq!(decode_unsigned(&encode_unsigned(value)).unwrap(), value);
+        }
+
+        #[test]
+        fn float_round_trip(value: f64) {
+            let decoded = decode_float(&encode_float(value)).unwrap();
+            prop_assert_eq!(decoded.to_bits(), value.to_bits());
+        }
+
+        #[test]
+        fn integer_uses_minimal_length(value: i64) {
+            let encoded = encode_integer(value);
+            let zigzag = ((value << 1) ^ (value >> 63)) as u64;
+            prop_assert_eq!(encoded.len(), 1 + size_hint(zigzag) as usize);
+        }
+    }
+}
@@ -0,0 +1,472 @@
+use crate::json::{DecodeError, MAX_ARRAY_GAP, ScalarRef, TAG_BYTES};
+use crate::path::PathError;
+use crate::{Error, PathSegment, PrefixDecoder};
+use std::io::{Read, Write};
+
+/// Writes entries of the `decoder` as JSON text into `writer`, without materializing the
+/// whole document as `serde_json::Value`.
+///
+/// Objects and arrays are opened and closed by comparing paths of consecutive entries, so
+/// entries of the same object/array must be adjacent (which is always true for sorted streams).
+/// Memory used is proportional to the depth of the document. Missing array elements are
+/// written as `null`, the same way [super::Merge] does it, up to [MAX_ARRAY_GAP] of them
+/// in a row.
+///
+/// Failures of the `writer` are reported as [Error::Io], while malformed entries are reported
+/// as [Error::InvalidPath] or [Error::InvalidValue].
+pub fn write_json<R: Read, W: Write>(
+    mut decoder: PrefixDecoder<R>,
+    writer: W,
+) -> Result<(), Error> {
+    let mut json = JsonWriter {
+        writer,
+        stack: Vec::new(),
+        string: None,
+        root_written: false,
+    };
+    let mut segments = Vec::new();
+    while let Some((path, value)) = decoder.read_next()? {
+        segments.clear();
+        for segment in path.iter() {
+            match segment? {
+                PathSegment::Key(key) => segments.push(Segment::Key(key.into())),
+                PathSegment::Index(index) => segments.push(Segment::Index(index)),
+                PathSegment::Cont => segments.push(Segment::Cont),
+            }
+        }
+        json.write_entry(&segments, value)?;
+    }
+    json.finish()
+}
+
+/// Owned version of [PathSegment].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(u64),
+    Cont,
+}
+
+/// JSON object or array that has been opened, but not closed yet.
+#[derive(Debug)]
+struct Frame {
+    array: bool,
+    /// Segment of the last member written into this object or array.
+    last: Option<Segment>,
+    /// Index of the next array element to be written.
+    next_index: u64,
+}
+
+/// String value split into continuation entries that has been opened, but not closed yet.
+#[derive(Debug)]
+struct OpenString {
+    next_offset: u64,
+    /// Bytes of a UTF-8 character split between two chunks, or bytes of a blob that didn't
+    /// fill a complete base64 group yet.
+    incomplete: Vec<u8>,
+    /// Value is a blob written as base64 string.
+    blob: bool,
+}
+
+struct JsonWriter<W> {
+    writer: W,
+    stack: Vec<Frame>,
+    string: Option<OpenString>,
+    root_written: bool,
+}
+
+impl<W: Write> JsonWriter<W> {
+    fn write_entry(&mut self, path: &[Segment], value: &[u8]) -> Result<(), Error> {
+        if let [parent @ .., Segment::Index(offset), Segment::Cont] = path {
+            if self.string.is_some() && self.is_open(parent) {
+                return self.write_chunk(*offset, value);
+            }
+            self.close_string()?;
+            self.navigate(parent)?;
+            self.writer.write_all(b"\"")?;
+            self.string = Some(OpenString {
+                next_offset: 0,
+                incomplete: Vec::new(),
+                blob: false,
+            });
+            self.write_chunk(*offset, value)
+        } else if value.first() == Some(&TAG_BYTES) {
+            // bytes value may be followed by continuation entries, see `write_blob`
+            self.close_string()?;
+            self.navigate(path)?;
+            self.writer.write_all(b"\"")?;
+            self.string = Some(OpenString {
+                next_offset: 0,
+                incomplete: Vec::new(),
+                blob: true,
+            });
+            self.write_chunk(0, &value[1..])
+        } else {
+            self.close_string()?;
+            self.navigate(path)?;
+            self.write_scalar(value)
+        }
+    }
+
+    /// Checks if path of the currently open value is equal to a given one.
+    fn is_open(&self, path: &[Segment]) -> bool {
+        path.len() == self.stack.len()
+            && self
+                .stack
+                .iter()
+                .zip(path)
+                .all(|(frame, segment)| frame.last.as_ref() == Some(segment))
+    }
+
+    /// Closes and opens objects and arrays, so that the next written value is placed at `path`.
+    fn navigate(&mut self, path: &[Segment]) -> Result<(), Error> {
+        if self.stack.is_empty() {
+            if self.root_written {
+                // stream contains more than one root value
+                return Err(PathError::Mismatch.into());
+            }
+            self.root_written = true;
+            match path.first() {
+                None => return Ok(()),
+                Some(segment) => self.open(segment)?,
+            }
+        }
+
+        // find the deepest container shared by the currently open path and the new one
+        let mut depth = 0;
+        while depth + 1 < self.stack.len()
+            && depth + 1 < path.len()
+            && self.stack[depth].last.as_ref() == Some(&path[depth])
+        {
+            depth += 1;
+        }
+        while self.stack.len() > depth + 1 {
+            self.close()?;
+        }
+        if path.is_empty() {
+            // root value mixed with object or array entries
+            return Err(PathError::Mismatch.into());
+        }
+
+        for (i, segment) in path.iter().enumerate().skip(depth) {
+            self.write_member(segment)?;
+            if let Some(next) = path.get(i + 1) {
+                self.open(next)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens a new object or array, depending on the segment of its first member.
+    fn open(&mut self, first: &Segment) -> Result<(), Error> {
+        let array = match first {
+            Segment::Key(_) => false,
+            Segment::Index(_) => true,
+            Segment::Cont => return Err(PathError::Mismatch.into()),
+        };
+        self.writer.write_all(if array { b"[" } else { b"{" })?;
+        self.stack.push(Frame {
+            array,
+            last: None,
+            next_index: 0,
+        });
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), Error> {
+        if let Some(frame) = self.stack.pop() {
+            self.writer
+                .write_all(if frame.array { b"]" } else { b"}" })?;
+        }
+        Ok(())
+    }
+
+    /// Writes separator and a key (for objects) of the next member of the innermost container.
+    fn write_member(&mut self, segment: &Segment) -> Result<(), Error> {
+        let frame = self.stack.last_mut().unwrap();
+        match (frame.array, segment) {
+            (false, Segment::Key(key)) => {
+                if frame.last.is_some() {
+                    self.writer.write_all(b",")?;
+                }
+                write_escaped(&mut self.writer, key.as_bytes(), true)?;
+                self.writer.write_all(b":")?;
+            }
+            (true, Segment::Index(index)) => {
+                if *index < frame.next_index {
+                    return Err(PathError::IndexOutOfOrder(*index).into());
+                }
+                if *index - frame.next_index > MAX_ARRAY_GAP {
+                    return Err(PathError::IndexGapTooLong(*index).into());
+                }
+                // fill the gaps left by the missing elements
+                for _ in frame.next_index..*index {
+                    if frame.last.is_some() {
+                        self.writer.write_all(b",")?;
+                    }
+                    self.writer.write_all(b"null")?;
+                    frame.last = Some(Segment::Index(frame.next_index));
+                    frame.next_index += 1;
+                }
+                if frame.last.is_some() {
+                    self.writer.write_all(b",")?;
+                }
+                frame.next_index = index + 1;
+            }
+            // object keys mixed with array indexes
+            _ => return Err(PathError::Mismatch.into()),
+        }
+        frame.last = Some(segment.clone());
+        Ok(())
+    }
+
+    fn write_scalar(&mut self, value: &[u8]) -> Result<(), Error> {
+        let writer = &mut self.writer;
+        match ScalarRef::decode(value)? {
+            ScalarRef::Null => writer.write_all(b"null"),
+            ScalarRef::Bool(true) => writer.write_all(b"true"),
+            ScalarRef::Bool(false) => writer.write_all(b"false"),
+            ScalarRef::Int(value) => write!(writer, "{value}"),
+            ScalarRef::UInt(value) => write!(writer, "{value}"),
+            // JSON has no representation for NaN and infinities
+            ScalarRef::Float(value) if !value.is_finite() => writer.write_all(b"null"),
+            ScalarRef::Float(value) => {
+                serde_json::to_writer(writer, &value).map_err(std::io::Error::from)
+            }
+            ScalarRef::Decimal(value) => writer.write_all(value.as_bytes()),
+            ScalarRef::Str(value) => write_escaped(writer, value.as_bytes(), true),
+            ScalarRef::Bytes(value) | ScalarRef::Chunk(value) => {
+                write!(writer, "\"{}\"", simple_base64::encode(value))
+            }
+            ScalarRef::EmptyObject => writer.write_all(b"{}"),
+            ScalarRef::EmptyArray => writer.write_all(b"[]"),
+        }
+        .map_err(Error::Io)
+    }
+
+    fn write_chunk(&mut self, offset: u64, chunk: &[u8]) -> Result<(), Error> {
+        let string = self.string.as_mut().unwrap();
+        if offset != string.next_offset {
+            return Err(DecodeError::UnexpectedOffset {
+                offset,
+                len: string.next_offset,
+            }
+            .into());
+        }
+        string.next_offset += chunk.len() as u64;
+
+        let mut buf = std::mem::take(&mut string.incomplete);
+        buf.extend_from_slice(chunk);
+        if string.blob {
+            // base64 encodes groups of 3 bytes, the rest has to wait for the next chunk
+            let complete = buf.len() - buf.len() % 3;
+            self.writer
+                .write_all(simple_base64::encode(&buf[..complete]).as_bytes())?;
+            string.incomplete.extend_from_slice(&buf[complete..]);
+            return Ok(());
+        }
+        let valid = match std::str::from_utf8(&buf) {
+            Ok(_) => buf.len(),
+            // character continues in the next chunk
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(DecodeError::InvalidUtf8(e).into()),
+        };
+        write_escaped(&mut self.writer, &buf[..valid], false)?;
+        string.incomplete.extend_from_slice(&buf[valid..]);
+        Ok(())
+    }
+
+    fn close_string(&mut self) -> Result<(), Error> {
+        if let Some(string) = self.string.take() {
+            if string.blob {
+                let tail = simple_base64::encode(&string.incomplete);
+                self.writer.write_all(tail.as_bytes())?;
+            } else if let Err(e) = std::str::from_utf8(&string.incomplete) {
+                // string ends with incomplete UTF-8 character
+                return Err(DecodeError::InvalidUtf8(e).into());
+            }
+            self.writer.write_all(b"\"")?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Error> {
+        self.close_string()?;
+        while !self.stack.is_empty() {
+            self.close()?;
+        }
+        if !self.root_written {
+            self.writer.write_all(b"null")?;
+        }
+        self.writer.flush().map_err(Error::Io)
+    }
+}
+
+/// Writes UTF-8 bytes as JSON string contents, escaping characters when necessary.
+fn write_escaped<W: Write>(writer: &mut W, bytes: &[u8], quoted: bool) -> std::io::Result<()> {
+    if quoted {
+        writer.write_all(b"\"")?;
+    }
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        let escaped: &[u8] = match byte {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0x08 => b"\\b",
+            0x0c => b"\\f",
+            0x00..=0x1f => {
+                writer.write_all(&bytes[start..i])?;
+                write!(writer, "\\u{byte:04x}")?;
+                start = i + 1;
+                continue;
+            }
+            _ => continue,
+        };
+        writer.write_all(&bytes[start..i])?;
+        writer.write_all(escaped)?;
+        start = i + 1;
+    }
+    writer.write_all(&bytes[start..])?;
+    if quoted {
+        writer.write_all(b"\"")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::json::{Flatten, Merge, write_json};
+    use crate::path::Encode;
+    use crate::{Error, JsonPath, PathError, PrefixDecoder};
+    use serde_json::json;
+
+    fn round_trip(value: serde_json::Value, chunk_size: usize) -> serde_json::Value {
+        let mut buf = Vec::new();
+        value.flatten(chunk_size).write_to(&mut buf).unwrap();
+        let mut json = Vec::new();
+        write_json(PrefixDecoder::new(buf.as_slice()), &mut json).unwrap();
+        serde_json::from_slice(&json).unwrap()
+    }
+
+    #[test]
+    fn write_json_complex() {
+        let expected: serde_json::Value =
+            serde_json::from_str(include_str!("../../assets/complex.json")).unwrap();
+        assert_eq!(round_trip(expected.clone(), u16::MAX as usize), expected);
+        assert_eq!(round_trip(expected.clone(), 100), expected);
+    }
+
+    #[test]
+    fn write_json_scalars() {
+        let expected = json!({
+            "a": [1, -2, 3.5, -1e-7, true, false, null, "\"quoted\"\n\u{1}"],
+            "b": { "c": [[1, [2]], { "d": { "e": [] } }, {}] },
+        });
+        assert_eq!(round_trip(expected.clone(), 100), expected);
+        assert_eq!(round_trip(json!("root"), 100), json!("root"));
+        assert_eq!(round_trip(json!(42), 100), json!(42));
+    }
+
+    #[test]
+    fn write_json_split_characters() {
+        // multibyte characters will end up split between continuation entries
+        let expected = json!({ "a": "zażółć gęślą jaźń 😀".repeat(20), "b": "é".repeat(50) });
+        for chunk_size in 20..30 {
+            assert_eq!(round_trip(expected.clone(), chunk_size), expected);
+        }
+    }
+
+    #[test]
+    fn write_json_blobs() {
+        let small: Vec<u8> = (0..100u8).collect();
+        let large: Vec<u8> = (0..150_000u32).map(|i| (i % 253) as u8).collect();
+        let mut buf = Vec::new();
+        let mut encoder = crate::PrefixEncoder::new(&mut buf);
+        encoder.write_blob(&[0, b'a'], small.as_slice()).unwrap();
+        encoder.write_blob(&[0, b'b'], large.as_slice()).unwrap();
+        encoder.write_blob(&[0, b'c'], &[][..]).unwrap();
+        let mut json = Vec::new();
+        write_json(PrefixDecoder::new(buf.as_slice()), &mut json).unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        let expected = json!({
+            "a": simple_base64::encode(&small),
+            "b": simple_base64::encode(&large),
+            "c": "",
+        });
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn write_json_malformed() {
+        let write = |entries: &[(&[u8], &[u8])]| {
+            let mut buf = Vec::new();
+            let mut encoder = crate::PrefixEncoder::new(&mut buf);
+            for (key, value) in entries {
+                encoder.write_next(key, value).unwrap();
+            }
+            write_json(PrefixDecoder::new(buf.as_slice()), std::io::sink())
+        };
+        let null: &[u8] = &[crate::json::TAG_NULL];
+
+        // 7-byte array index, which would take ~2^44 nulls to fill the gap
+        let gap = [0, b'a', 0x17, 0x00, 0x0f, 0xff, 0xff, 0xff, 0xff, 0xff];
+        let res = write(&[(&gap, null)]);
+        assert!(matches!(
+            res,
+            Err(Error::InvalidPath(PathError::IndexGapTooLong(_)))
+        ));
+
+        // object key followed by an array index at the same level
+        let res = write(&[(&[0, b'a'], null), (&[0x11, 1], null)]);
+        assert!(matches!(res, Err(Error::InvalidPath(PathError::Mismatch))));
+
+        let res = write(&[(&[0, b'a'], &[0xff])]);
+        assert!(matches!(res, Err(Error::InvalidValue(_))));
+
+        // broken output is reported as I/O error
+        struct Broken;
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let mut buf = Vec::new();
+        json!({ "a": 1 }).flatten(100).write_to(&mut buf).unwrap();
+        let res = write_json(PrefixDecoder::new(buf.as_slice()), Broken);
+        assert!(matches!(res, Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn write_json_filtered() {
+        let json_path = JsonPath::parse("users[*].name").unwrap();
+        let source = json!({
+            "users": [
+                { "name": "Alice", "age": 25 },
+                { "name": "Bob", "age": 30 },
+                { "nick": "crocodile91", "age": 35 },
+                { "name": "Damian", "age": 30 },
+            ]
+        });
+        let entries: Vec<_> = source
+            .flatten(100)
+            .filter(|(path, _)| json_path.is_match(&path.as_path()))
+            .collect();
+        let mut buf = Vec::new();
+        entries.clone().into_iter().write_to(&mut buf).unwrap();
+        let mut json = Vec::new();
+        write_json(PrefixDecoder::new(buf.as_slice()), &mut json).unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        let expected = entries
+            .into_iter()
+            .map(|(path, value)| (path.into_path(), value))
+            .merge();
+        assert_eq!(actual, expected);
+    }
+}
@@ -2,7 +2,19 @@ use crate::json_path::JsonPathToken;
 use crate::{JsonPath, Path, PathSegment};
 
 impl<'a> JsonPath<'a> {
+    /// Checks if `path` matches this JSON Path. Filter predicates need entry values to be
+    /// evaluated, so here they match any child - use [JsonPath::is_match_entry] to apply them.
     pub fn is_match(&self, path: &Path) -> bool {
+        self.matches(path, None)
+    }
+
+    /// Checks if an entry matches this JSON Path, including its filter predicates, which
+    /// compare them against the entry `value`.
+    pub fn is_match_entry(&self, path: &Path, value: &[u8]) -> bool {
+        self.matches(path, Some(value))
+    }
+
+    fn matches(&self, path: &Path, value: Option<&[u8]>) -> bool {
         let mut iter = Vec::new();
         for segment in path.iter() {
             match segment {
@@ -11,7 +23,42 @@ impl<'a> JsonPath<'a> {
             }
         }
 
-        match_path_inner(self.as_ref(), 0, &iter, 0)
+        match_path_inner(self.as_ref(), 0, &iter, 0, value)
+    }
+
+    /// Returns the length of the shortest prefix of the encoded `path` which rules out a match,
+    /// so that no path under it can match either, or `None` if there's no such prefix.
+    pub(crate) fn mismatch_len(&self, path: &Path) -> Option<usize> {
+        let mut segments = path.iter();
+        for token in self.as_ref() {
+            let matches = match token {
+                JsonPathToken::Root | JsonPathToken::Current => continue,
+                // descendants at any depth can match
+                JsonPathToken::RecursiveDescend => return None,
+                JsonPathToken::Wildcard | JsonPathToken::Filter(_) => segments.next()?.is_ok(),
+                token => match (token, segments.next()?.ok()?) {
+                    (JsonPathToken::Member(key1), PathSegment::Key(key2)) => *key1 == key2,
+                    (JsonPathToken::Index(index1), PathSegment::Index(index2)) => {
+                        *index1 == index2 as i64
+                    }
+                    (JsonPathToken::Slice(from, to, _), PathSegment::Index(i)) => {
+                        (*from..*to).contains(&i)
+                    }
+                    (JsonPathToken::MemberUnion(keys), PathSegment::Key(key)) => {
+                        keys.contains(&key)
+                    }
+                    (JsonPathToken::IndexUnion(indices), PathSegment::Index(index)) => {
+                        indices.contains(&(index as i64))
+                    }
+                    _ => false,
+                },
+            };
+            if !matches {
+                return Some(segments.offset());
+            }
+        }
+        // paths under a matching path match as well
+        None
     }
 }
 
@@ -20,6 +67,7 @@ fn match_path_inner<'a>(
     mut token_index: usize,
     path: &[PathSegment<'a>],
     mut path_index: usize,
+    value: Option<&[u8]>,
 ) -> bool {
     while token_index < tokens.len() {
         match tokens[token_index] {
@@ -55,7 +103,7 @@ fn match_path_inner<'a>(
             JsonPathToken::RecursiveDescend => {
                 // Recursive descend logic can be complex, simplified here
                 for i in path_index..path.len() {
-                    if match_path_inner(tokens, token_index + 1, path, i) {
+                    if match_path_inner(tokens, token_index + 1, path, i, value) {
                         return true; // Found a match deeper in the path
                     }
                 }
@@ -65,14 +113,14 @@ fn match_path_inner<'a>(
                 // Simplified for now, assuming it matches any segment in range
                 let range = from..to;
                 match path.get(path_index) {
-                    Some(PathSegment::Index(i)) if range.contains(&i) => { /* continue */ }
+                    Some(PathSegment::Index(i)) if range.contains(i) => { /* continue */ }
                     _ => return false,
                 }
                 path_index += 1; // Move to the next segment
             }
             JsonPathToken::MemberUnion(ref keys) => {
                 if let Some(PathSegment::Key(key)) = path.get(path_index) {
-                    if !keys.contains(&key) {
+                    if !keys.contains(key) {
                         return false; // Segment not in member union
                     }
                 } else {
@@ -80,6 +128,21 @@ fn match_path_inner<'a>(
                 }
                 path_index += 1; // Move to the next segment
             }
+            JsonPathToken::Filter(ref predicate) => {
+                // predicates apply to the scalar value of the entry, so the filtered child
+                // must be the last segment of its path
+                match path.get(path_index) {
+                    Some(PathSegment::Key(_) | PathSegment::Index(_))
+                        if path_index + 1 == path.len() =>
+                    {
+                        if value.is_some_and(|value| !predicate.eval(value)) {
+                            return false;
+                        }
+                    }
+                    _ => return false,
+                }
+                path_index += 1;
+            }
             JsonPathToken::IndexUnion(ref indices) => {
                 if let Some(PathSegment::Index(index)) = path.get(path_index) {
                     if !indices.contains(&(*index as i64)) {
@@ -1,6 +1,8 @@
 mod filter;
 mod parse;
 
+use crate::json::{ScalarRef, Value};
+use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -25,6 +27,66 @@ pub(super) enum JsonPathToken<'a> {
     Slice(u64, u64, u64),
     MemberUnion(Vec<&'a str>),
     IndexUnion(Vec<i64>),
+    Filter(Predicate<'a>),
+}
+
+/// Filter expression `?(@ <op> <literal>)`, which matches children whose scalar value compares
+/// to a literal. Numbers are compared exactly, no matter if they are stored as integers, floats
+/// or decimals.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Predicate<'a> {
+    op: CompareOp,
+    /// Literal as written in the JSON Path.
+    literal: &'a str,
+    /// Encoded value of the literal.
+    value: Value,
+}
+
+impl<'a> Predicate<'a> {
+    pub(super) fn new(op: CompareOp, literal: &'a str, value: Value) -> Self {
+        Self { op, literal, value }
+    }
+
+    /// Checks if encoded entry `value` satisfies the predicate. Values of different types
+    /// are never equal nor ordered.
+    pub(super) fn eval(&self, value: &[u8]) -> bool {
+        let (Ok(value), Ok(literal)) = (ScalarRef::decode(value), ScalarRef::decode(&self.value))
+        else {
+            return false;
+        };
+        let ordering = value.partial_cmp(&literal);
+        match self.op {
+            CompareOp::Eq => ordering == Some(Ordering::Equal),
+            CompareOp::Ne => ordering != Some(Ordering::Equal),
+            CompareOp::Lt => ordering == Some(Ordering::Less),
+            CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Gt => ordering == Some(Ordering::Greater),
+            CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    pub(super) fn as_str(&self) -> &'static str {
+        match self {
+            CompareOp::Eq => "==",
+            CompareOp::Ne => "!=",
+            CompareOp::Lt => "<",
+            CompareOp::Le => "<=",
+            CompareOp::Gt => ">",
+            CompareOp::Ge => ">=",
+        }
+    }
 }
 
 impl<'a> Display for JsonPathToken<'a> {
@@ -49,7 +111,7 @@ impl<'a> Display for JsonPathToken<'a> {
                 if let Some(m) = i.next() {
                     write!(f, "{}", m)?;
                 }
-                while let Some(m) = i.next() {
+                for m in i {
                     write!(f, ", {}", m)?;
                 }
                 write!(f, "]")
@@ -60,11 +122,14 @@ impl<'a> Display for JsonPathToken<'a> {
                 if let Some(m) = i.next() {
                     write!(f, "{}", m)?;
                 }
-                while let Some(m) = i.next() {
+                for m in i {
                     write!(f, ", {}", m)?;
                 }
                 write!(f, "]")
             }
+            JsonPathToken::Filter(predicate) => {
+                write!(f, "[?(@ {} {})]", predicate.op.as_str(), predicate.literal)
+            }
         }
     }
 }
@@ -77,9 +142,9 @@ pub enum ParseError {
 
 #[cfg(test)]
 mod test {
-    use crate::PathBuf;
     use crate::json::{Flatten, TAG_STRING};
     use crate::json_path::JsonPath;
+    use crate::{PathBuf, PathSegment, PrefixDecoder, PrefixEncoder};
     use serde_json::json;
     use smallvec::SmallVec;
 
@@ -116,18 +181,17 @@ mod test {
             ]
         })
         .flatten(100)
-        .into_iter()
     }
 
-    const BYTESTRING_BOB: &'static [u8] = &[TAG_STRING, b'B', b'o', b'b'];
-    const BYTESTRING_ALICE: &'static [u8] = &[TAG_STRING, b'A', b'l', b'i', b'c', b'e'];
-    const BYTESTRING_DAMIAN: &'static [u8] = &[TAG_STRING, b'D', b'a', b'm', b'i', b'a', b'n'];
-    const BYTESTRING_ELISE: &'static [u8] = &[TAG_STRING, b'E', b'l', b'i', b's', b'e'];
-    const BYTESTRING_BOREAS: &'static [u8] = &[TAG_STRING, b'b', b'o', b'r', b'e', b'a', b's'];
-    const BYTESTRING_CROCODILE91: &'static [u8] = &[
+    const BYTESTRING_BOB: &[u8] = &[TAG_STRING, b'B', b'o', b'b'];
+    const BYTESTRING_ALICE: &[u8] = &[TAG_STRING, b'A', b'l', b'i', b'c', b'e'];
+    const BYTESTRING_DAMIAN: &[u8] = &[TAG_STRING, b'D', b'a', b'm', b'i', b'a', b'n'];
+    const BYTESTRING_ELISE: &[u8] = &[TAG_STRING, b'E', b'l', b'i', b's', b'e'];
+    const BYTESTRING_BOREAS: &[u8] = &[TAG_STRING, b'b', b'o', b'r', b'e', b'a', b's'];
+    const BYTESTRING_CROCODILE91: &[u8] = &[
         TAG_STRING, b'c', b'r', b'o', b'c', b'o', b'd', b'i', b'l', b'e', b'9', b'1',
     ];
-    const BYTESTRING_SMITH: &'static [u8] = &[TAG_STRING, b'S', b'm', b'i', b't', b'h'];
+    const BYTESTRING_SMITH: &[u8] = &[TAG_STRING, b'S', b'm', b'i', b't', b'h'];
 
     #[test]
     fn eval_member_partial() {
@@ -266,8 +330,7 @@ mod test {
                 }
             }
         })
-        .flatten(100)
-        .into_iter();
+        .flatten(100);
         let path = JsonPath::parse("$..c..name").unwrap();
         let values: Vec<_> = any
             .filter(|(p, _)| path.is_match(&p.as_path()))
@@ -281,4 +344,117 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn eval_filter_predicates() {
+        let sample = json!({
+            "prices": [1, 2.5, 10, 10.0, 11, "10", 1e2, null, { "a": 10 }],
+            "names": { "a": "Alice", "b": "Bob" }
+        });
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf);
+        for (path, value) in sample.clone().flatten(100) {
+            encoder.write_next(path.as_bytes(), &value).unwrap();
+        }
+        let eval = |query: &str| {
+            let path = JsonPath::parse(query).unwrap();
+            let expected: Vec<_> = sample
+                .clone()
+                .flatten(100)
+                .filter(|(p, v)| path.is_match_entry(&p.as_path(), v))
+                .map(|(p, _)| p.as_path().to_string())
+                .collect();
+            let mut decoder = PrefixDecoder::new(buf.as_slice());
+            let mut actual = Vec::new();
+            while let Some((p, _)) = decoder.read_next_matching(&path).unwrap() {
+                actual.push(p.to_string());
+            }
+            assert_eq!(actual, expected, "{query}");
+            actual
+        };
+        assert_eq!(
+            eval("$.prices[?(@ >= 10)]"),
+            vec!["$.prices[2]", "$.prices[3]", "$.prices[4]", "$.prices[6]"]
+        );
+        assert_eq!(eval("$.prices[?(@ < 2.5)]"), vec!["$.prices[0]"]);
+        assert_eq!(eval("$.prices[?(@ == '10')]"), vec!["$.prices[5]"]);
+        assert_eq!(eval("$.prices[?(@ == null)]"), vec!["$.prices[7]"]);
+        assert_eq!(eval("$.names[?(@ > 'B')]"), vec!["$.names.b"]);
+        assert_eq!(
+            eval("$..[?(@ == 10)]"),
+            vec!["$.prices[2]", "$.prices[3]", "$.prices[8].a"]
+        );
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn eval_filter_decimals() {
+        let sample: serde_json::Value =
+            serde_json::from_str(r#"[0.1, 0.10000000000000000001, 100000000000000000000]"#)
+                .unwrap();
+        let path = JsonPath::parse("$[?(@ > 0.1)]").unwrap();
+        let matches: Vec<_> = sample
+            .flatten(100)
+            .filter(|(p, v)| path.is_match_entry(&p.as_path(), v))
+            .map(|(p, _)| p.as_path().to_string())
+            .collect();
+        assert_eq!(matches, vec!["$[1]", "$[2]"]);
+    }
+
+    #[test]
+    fn eval_decoder_skips_branches() {
+        let sample = json!({
+            "items": (0..50).map(|i| json!({
+                "id": format!("video-{i}"),
+                "snippet": {
+                    "thumbnails": {
+                        "default": { "url": format!("https://img/{i}/default.jpg"), "width": 120 },
+                        "high": { "url": format!("https://img/{i}/high.jpg"), "width": 480 }
+                    },
+                    "title": format!("title-{i}")
+                }
+            })).collect::<Vec<_>>(),
+            "kind": "searchListResponse"
+        });
+        let mut buf = Vec::new();
+        let mut encoder = PrefixEncoder::new(&mut buf).with_restart_interval(7);
+        for (path, value) in sample.clone().flatten(100) {
+            encoder.write_next(path.as_bytes(), &value).unwrap();
+        }
+        encoder.finish().unwrap();
+
+        for query in [
+            "$.items[*].id",
+            "$.items[2:4].snippet.title",
+            "$.items[*].snippet.thumbnails.high",
+            "$.kind",
+            "$..url",
+        ] {
+            let path = JsonPath::parse(query).unwrap();
+            let expected: Vec<_> = sample
+                .clone()
+                .flatten(100)
+                .filter(|(p, _)| path.is_match(&p.as_path()))
+                .map(|(p, v)| (p, v.to_vec()))
+                .collect();
+            let mut decoder = PrefixDecoder::new(buf.as_slice());
+            let mut values = Vec::new();
+            while let Some((p, v)) = decoder.read_next_matching(&path).unwrap() {
+                values.push((p.as_path_buf(), v.to_vec()));
+            }
+            assert_eq!(values, expected, "{query}");
+        }
+
+        let path = JsonPath::parse("$.items[*].id").unwrap();
+        let thumbnail = PathBuf::from_iter([
+            PathSegment::Key("items"),
+            PathSegment::Index(3),
+            PathSegment::Key("snippet"),
+            PathSegment::Key("thumbnails"),
+        ]);
+        let snippet_len = thumbnail.as_bytes().len() - "thumbnails".len() - 1;
+        assert_eq!(path.mismatch_len(&thumbnail.as_path()), Some(snippet_len));
+        let descent = JsonPath::parse("$..id").unwrap();
+        assert_eq!(descent.mismatch_len(&thumbnail.as_path()), None);
+    }
 }
@@ -1,5 +1,6 @@
 use crate::JsonPath;
-use crate::json_path::{JsonPathToken, ParseError};
+use crate::json::scalar::{encode_bool, encode_null, encode_number, encode_str};
+use crate::json_path::{CompareOp, JsonPathToken, ParseError, Predicate};
 use std::str::FromStr;
 
 impl<'a> JsonPath<'a> {
@@ -81,11 +82,9 @@ impl<'a> JsonPath<'a> {
                     } else if let Ok(index) = slice.parse::<i64>() {
                         // '[{number}]' => array index
                         tokens.push(JsonPathToken::Index(index));
-                    } else if slice.starts_with('?') {
-                        return Err(ParseError::InvalidJsonPath(format!(
-                            "JSON Path predicate expressions are not supported yet: `{}`",
-                            slice
-                        )));
+                    } else if let Some(expr) = slice.strip_prefix('?') {
+                        // '[?(@ {op} {literal})]' => filter by value
+                        tokens.push(JsonPathToken::Filter(parse_predicate(expr)?));
                     } else if slice.contains(':') {
                         // '[{?from}:{?to}:{?by}]' => slice operator
                         let mut split = slice.split(':');
@@ -189,6 +188,53 @@ impl<'a> JsonPath<'a> {
     }
 }
 
+/// Parses filter expression `(@ {op} {literal})`, where literal is a JSON number, string
+/// (in single or double quotes), boolean or null.
+fn parse_predicate(expr: &str) -> Result<Predicate<'_>, ParseError> {
+    let unsupported = || {
+        ParseError::InvalidJsonPath(format!(
+            "only `?(@ <op> <literal>)` predicate expressions are supported: `?{}`",
+            expr
+        ))
+    };
+    let inner = expr
+        .strip_prefix('(')
+        .and_then(|e| e.strip_suffix(')'))
+        .and_then(|e| e.trim().strip_prefix('@'))
+        .ok_or_else(unsupported)?
+        .trim_start();
+    let (op, literal) = [
+        ("==", CompareOp::Eq),
+        ("!=", CompareOp::Ne),
+        ("<=", CompareOp::Le),
+        (">=", CompareOp::Ge),
+        ("<", CompareOp::Lt),
+        (">", CompareOp::Gt),
+    ]
+    .into_iter()
+    .find_map(|(prefix, op)| Some((op, inner.strip_prefix(prefix)?.trim())))
+    .ok_or_else(unsupported)?;
+    let quoted = |q: char| {
+        literal
+            .strip_prefix(q)
+            .and_then(|l| l.strip_suffix(q))
+            .filter(|_| literal.len() >= 2)
+    };
+    let value = match literal {
+        "true" => encode_bool(true),
+        "false" => encode_bool(false),
+        "null" => encode_null(),
+        _ => match quoted('\'').or_else(|| quoted('"')) {
+            Some(str) => encode_str(str),
+            None => match serde_json::Number::from_str(literal) {
+                Ok(number) => encode_number(&number),
+                Err(_) => return Err(unsupported()),
+            },
+        },
+    };
+    Ok(Predicate::new(op, literal, value))
+}
+
 fn invalid_char(c: char, path: &str) -> ParseError {
     ParseError::InvalidJsonPath(format!("Invalid character `{}` in path: `{}`", c, path))
 }
@@ -372,6 +418,34 @@ mod test {
         );
     }
 
+    #[test]
+    fn parse_predicate() {
+        use crate::json::scalar::{encode_integer, encode_str};
+        use crate::json_path::{CompareOp, Predicate};
+
+        let path = JsonPath::parse("$.prices[?(@ >= 10)]").unwrap();
+        assert_eq!(
+            path.tokens,
+            vec![
+                JsonPathToken::Root,
+                JsonPathToken::Member("prices"),
+                JsonPathToken::Filter(Predicate::new(CompareOp::Ge, "10", encode_integer(10)))
+            ]
+        );
+        let path = JsonPath::parse("$.name[?( @!='Alice' )]").unwrap();
+        assert_eq!(
+            path.tokens[2],
+            JsonPathToken::Filter(Predicate::new(
+                CompareOp::Ne,
+                "'Alice'",
+                encode_str("Alice")
+            ))
+        );
+        assert_eq!(path.tokens[2].to_string(), "[?(@ != 'Alice')]");
+        assert!(JsonPath::parse("$.a[?(@ ~ 1)]").is_err());
+        assert!(JsonPath::parse("$.a[?(@ < abc)]").is_err());
+    }
+
     #[test]
     fn parse_predicate_error() {
         let res = JsonPath::parse("$.users[?(@['name'] == 'Alice')].surname");
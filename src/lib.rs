@@ -1,11 +1,38 @@
+#[cfg(feature = "async")]
+mod async_io;
+mod blob;
+mod checksum;
+mod chunked;
+mod compression;
+mod cursor;
+pub mod de;
 mod encoding;
+mod error;
+mod extension;
+mod header;
+mod index;
 pub mod json;
 mod json_path;
 mod path;
+mod reverse;
+pub mod ser;
+mod slice;
 
-pub use encoding::{PrefixDecoder, PrefixEncoder};
+#[cfg(feature = "async")]
+pub use async_io::{AsyncPrefixDecoder, AsyncPrefixEncoder};
+pub use blob::BlobReader;
+pub use chunked::ChunkedValueReader;
+pub use cursor::DecoderCursor;
+pub use encoding::{DecoderLimits, PrefixDecoder, PrefixEncoder};
+pub use error::Error;
+pub use extension::{DecodedItem, Extension, ExtensionEntry, MIN_USER_ID};
+pub use header::StreamHeader;
 pub use json_path::JsonPath;
-pub use path::{Path, PathBuf, PathSegment};
+pub use path::{Encode, Path, PathBuf, PathError, PathSegment};
+pub use reverse::ReverseDecoder;
+#[cfg(feature = "mmap")]
+pub use slice::MappedFile;
+pub use slice::SliceDecoder;
 
 fn size_hint(n: u64) -> u8 {
     match n {
@@ -41,6 +41,21 @@ impl<'a> Path<'a> {
         let buf = Vec::from(self.as_bytes());
         PathBuf::new(buf)
     }
+
+    /// Returns the length of the value path and the chunk offset if this path points to
+    /// a continuation entry (`path[offset]..`).
+    pub(crate) fn split_chunk(&self) -> Option<(usize, u64)> {
+        let mut iter = self.iter();
+        let mut last = None;
+        loop {
+            let start = iter.offset();
+            match iter.next()? {
+                Ok(PathSegment::Index(offset)) => last = Some((start, offset)),
+                Ok(PathSegment::Cont) if iter.offset() == self.as_bytes().len() => return last,
+                _ => last = None,
+            }
+        }
+    }
 }
 
 impl<'a> Display for Path<'a> {
@@ -51,8 +66,8 @@ impl<'a> Display for Path<'a> {
             match segment {
                 Ok(segment) => write!(f, "{}", segment)?,
                 Err(e) => {
-                    eprintln!("{e}");
-                    return Err(std::fmt::Error);
+                    // malformed segment ends the path, as there's no telling where it ends
+                    return write!(f, "<{e}>");
                 }
             }
         }
@@ -70,6 +85,11 @@ impl<'a> PathIter<'a> {
         Self { buf, pos: 0 }
     }
 
+    /// Byte offset of the next segment within the path.
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
     fn consume_key(&mut self) -> Result<&'a str, PathError> {
         let start = self.pos;
         while self.pos < self.buf.len()
@@ -91,7 +111,7 @@ impl<'a> PathIter<'a> {
         if byte_len == 0 {
             return Ok(0); // 0 is encoded as a single byte
         }
-        if self.pos + byte_len >= self.buf.len() {
+        if self.pos + byte_len > self.buf.len() {
             return Err(PathError::Eof);
         }
 
@@ -125,6 +145,12 @@ impl<'a> Iterator for PathIter<'a> {
     }
 }
 
+/// Returns whether the first `len` bytes of the encoded path `key` end at a segment boundary,
+/// i.e. the following byte, if any, isn't a continuation of a key segment.
+pub(crate) fn is_segment_boundary(key: &[u8], len: usize) -> bool {
+    key.get(len).is_none_or(|b| *b <= MAX_INDEX_BYTES)
+}
+
 /// ASCII extension tag, used to indicate that the key segment is ASCII-only.
 const ASCII_EXT: u8 = 0b1000_0000;
 
@@ -199,7 +225,7 @@ impl<W: Write> PathBuf<W> {
     }
 
     pub fn push_key(&mut self, key: &str) -> std::io::Result<()> {
-        self.writer.write(&[TAG_KEY])?;
+        self.writer.write_all(&[TAG_KEY])?;
         self.writer.write_all(key.as_bytes())?;
         Ok(())
     }
@@ -222,8 +248,8 @@ impl<W: Write> PathBuf<W> {
     }
 }
 
-impl PathBuf<Vec<u8>> {
-    pub fn from_iter<'a, I>(iter: I) -> Self
+impl<'a> FromIterator<PathSegment<'a>> for PathBuf<Vec<u8>> {
+    fn from_iter<I>(iter: I) -> Self
     where
         I: IntoIterator<Item = PathSegment<'a>>,
     {
@@ -238,7 +264,9 @@ impl PathBuf<Vec<u8>> {
         }
         path_buf
     }
+}
 
+impl PathBuf<Vec<u8>> {
     pub fn as_path(&self) -> Path<'_> {
         Path::from_slice(&self.writer)
     }
@@ -246,6 +274,12 @@ impl PathBuf<Vec<u8>> {
     pub fn into_path(self) -> Path<'static> {
         Path::from_vec(self.writer)
     }
+
+    /// Shortens the path to the first `len` bytes. `len` must point at a segment boundary.
+    #[inline]
+    pub fn truncate(&mut self, len: usize) {
+        self.writer.truncate(len);
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -260,6 +294,12 @@ pub enum PathError {
     InvalidIndex(#[from] std::num::TryFromIntError),
     #[error("path length exceeds 32KiB limit")]
     PathTooLong,
+    #[error("path doesn't match the structure of the preceding entries")]
+    Mismatch,
+    #[error("array index {0} is out of order")]
+    IndexOutOfOrder(u64),
+    #[error("array index {0} leaves too many missing elements before it")]
+    IndexGapTooLong(u64),
 }
 
 pub trait Encode {
@@ -272,9 +312,9 @@ where
     B1: AsRef<[u8]>,
     B2: AsRef<[u8]>,
 {
-    fn write_to<W: Write>(mut self, writer: &mut W) -> std::io::Result<()> {
+    fn write_to<W: Write>(self, writer: &mut W) -> std::io::Result<()> {
         let mut encoder = PrefixEncoder::new(writer);
-        while let Some((path_buf, value)) = self.next() {
+        for (path_buf, value) in self {
             let key = path_buf.as_ref();
             let key = key.as_ref();
             let value = value.as_ref();
@@ -307,6 +347,24 @@ mod test {
         assert!(iter.next().is_none());
     }
 
+    #[test]
+    fn path_ends_with_index() {
+        let path_buf = PathBuf::from_iter([PathSegment::Key("users"), 300u64.into()]);
+        let path = path_buf.as_path();
+        let mut iter = path.iter();
+        assert_eq!(iter.next().unwrap().unwrap(), PathSegment::Key("users"));
+        assert_eq!(iter.next().unwrap().unwrap(), PathSegment::Index(300));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn path_display_malformed() {
+        let path = Path::from_slice(&[0, b'a', 0x12, 1]);
+        assert_eq!(path.to_string(), "$.a<unexpected end of path data>");
+        let path = Path::from_slice(&[0x20]);
+        assert_eq!(path.to_string(), "$<unsupported path segment tag: 32>");
+    }
+
     #[test]
     fn path_keeps_lexical_order() {
         let a = PathBuf::from_iter([PathSegment::Key("users"), 1u64.into(), "name".into()]);
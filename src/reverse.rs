@@ -0,0 +1,229 @@
+use crate::{Error, PathBuf, PrefixDecoder};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Decoder returning entries of a stream in reverse order, starting from its end.
+///
+/// Prefix compression only allows decoding forward, so the stream is decoded block by block,
+/// each block spanning from a restart point written by
+/// [crate::PrefixEncoder::with_restart_interval] up to the next one, and the entries of every
+/// block are returned backwards. Restart points are taken from the index written by
+/// [crate::PrefixEncoder::with_index] if there is one, or found by searching the stream
+/// backwards for their markers otherwise. Memory used by the decoder is bounded by the size of
+/// a block, so streams without restart points, which would have to be decoded as a single
+/// block, are rejected.
+pub struct ReverseDecoder<R> {
+    decoder: PrefixDecoder<R>,
+    /// Restart points from the index, if the stream has one.
+    restarts: Option<Vec<u64>>,
+    /// Position of the restart point of the last decoded block, or the end of the stream.
+    end: u64,
+    /// Entries of the current block, the last one is returned next.
+    block: Vec<(PathBuf<Vec<u8>>, Vec<u8>)>,
+    done: bool,
+}
+
+impl<R: Read + Seek> ReverseDecoder<R> {
+    /// Creates a decoder of a stream, which must start at the beginning of `reader`. Fails with
+    /// [Error::MissingIndex] if the stream has neither an index nor any restart points.
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let mut decoder = PrefixDecoder::new(reader);
+        let restarts = match decoder.restart_points() {
+            Ok(restarts) => Some(restarts),
+            Err(Error::MissingIndex) => None,
+            Err(e) => return Err(e),
+        };
+        let end = decoder.reader_mut().seek(SeekFrom::End(0))?;
+        if restarts.is_none() && decoder.restart_before(end)?.is_none() {
+            return Err(Error::MissingIndex);
+        }
+        Ok(Self {
+            decoder,
+            restarts,
+            end,
+            block: Vec::new(),
+            done: false,
+        })
+    }
+
+    /// Decodes the block preceding the last decoded one.
+    fn read_block(&mut self) -> Result<(), Error> {
+        let start = match &self.restarts {
+            Some(restarts) => {
+                let i = restarts.partition_point(|offset| *offset < self.end);
+                restarts[..i].last().copied()
+            }
+            None => self.decoder.restart_before(self.end)?,
+        };
+        // entries before the first restart point are decoded from the start of the stream
+        self.decoder.restart_at(start.unwrap_or(0))?;
+        while let Some((path, value)) = self.decoder.read_next()? {
+            let entry = (path.as_path_buf(), value.to_vec());
+            if self.decoder.entry_position() >= self.end {
+                break;
+            }
+            self.block.push(entry);
+        }
+        self.end = start.unwrap_or(0);
+        self.done = self.end == 0;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Iterator for ReverseDecoder<R> {
+    type Item = Result<(PathBuf<Vec<u8>>, Vec<u8>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.block.pop() {
+                return Some(Ok(entry));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.read_block() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReverseDecoder;
+    use crate::index::RESTART_MARKER;
+    use crate::{Error, PathBuf, PathSegment, PrefixDecoder, PrefixEncoder, StreamHeader};
+    use std::io::Cursor;
+
+    type Entries = Vec<(PathBuf<Vec<u8>>, Vec<u8>)>;
+
+    fn event(i: u64) -> PathBuf<Vec<u8>> {
+        PathBuf::from_iter([PathSegment::Key("events"), i.into(), "id".into()])
+    }
+
+    fn encode(mut encoder: PrefixEncoder<Vec<u8>>, events: u64, fake_restart: bool) -> Vec<u8> {
+        let kind = PathBuf::from_iter([PathSegment::Key("kind")]);
+        encoder.write_next(kind.as_ref(), b"events").unwrap();
+        for i in 0..events {
+            if fake_restart && i == 42 {
+                // binary values containing restart markers, which must not be taken for
+                // restart points
+                encoder
+                    .write_next(event(i).as_ref(), &RESTART_MARKER)
+                    .unwrap();
+                let mut blob = b"binary ".to_vec();
+                blob.extend_from_slice(&RESTART_MARKER);
+                blob.extend_from_slice(b" data");
+                let payload =
+                    PathBuf::from_iter([PathSegment::Key("events"), i.into(), "payload".into()]);
+                encoder
+                    .write_blob(payload.as_ref(), blob.as_slice())
+                    .unwrap();
+                continue;
+            }
+            let value = format!("event-{i}").into_bytes();
+            encoder.write_next(event(i).as_ref(), &value).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn decode_forward(buf: &[u8]) -> Entries {
+        let mut decoder = PrefixDecoder::new(buf);
+        let mut entries = Vec::new();
+        while let Some((path, value)) = decoder.read_next().unwrap() {
+            entries.push((path.as_path_buf(), value.to_vec()));
+        }
+        entries
+    }
+
+    #[test]
+    fn reverse_matches_forward() {
+        let header = StreamHeader::new(StreamHeader::FLAG_CHECKSUMS);
+        let with_header = || PrefixEncoder::with_header(Vec::new(), header).unwrap();
+        let streams = [
+            encode(
+                PrefixEncoder::new(Vec::new()).with_restart_interval(1),
+                300,
+                false,
+            ),
+            encode(
+                PrefixEncoder::new(Vec::new()).with_restart_interval(4),
+                300,
+                true,
+            ),
+            encode(with_header().with_index(16).with_checksums(10), 300, true),
+            encode(
+                with_header().with_restart_interval(7).with_checksums(5),
+                300,
+                false,
+            ),
+            encode(
+                with_header().with_restart_interval(7).with_checksums(5),
+                0,
+                false,
+            ),
+            // enough entries for several windows of the backward search
+            encode(
+                PrefixEncoder::new(Vec::new()).with_restart_interval(5000),
+                20_000,
+                false,
+            ),
+        ];
+        #[cfg(feature = "lz4")]
+        let compressed = StreamHeader::new(header.flags | StreamHeader::FLAG_COMPRESSION);
+        #[cfg(feature = "lz4")]
+        let streams = streams.into_iter().chain([
+            encode(
+                PrefixEncoder::with_header(Vec::new(), compressed)
+                    .unwrap()
+                    .with_index(16)
+                    .with_checksums(10)
+                    .with_compression(512),
+                300,
+                false,
+            ),
+            // restart points without index are found behind compressed blocks
+            encode(
+                PrefixEncoder::with_header(Vec::new(), compressed)
+                    .unwrap()
+                    .with_restart_interval(10)
+                    .with_checksums(10)
+                    .with_compression(512),
+                300,
+                true,
+            ),
+        ]);
+        for (i, buf) in streams.into_iter().enumerate() {
+            let mut expected = decode_forward(&buf);
+            expected.reverse();
+            let decoder = ReverseDecoder::new(Cursor::new(buf)).unwrap();
+            let entries: Entries = decoder.map(Result::unwrap).collect();
+            assert_eq!(entries, expected, "stream {i}");
+        }
+    }
+
+    #[test]
+    fn reverse_without_restarts() {
+        let buf = encode(PrefixEncoder::new(Vec::new()), 300, false);
+        let res = ReverseDecoder::new(Cursor::new(buf));
+        assert!(matches!(res, Err(Error::MissingIndex)));
+    }
+
+    #[test]
+    fn reverse_last_events() {
+        let buf = encode(PrefixEncoder::new(Vec::new()).with_index(10), 1000, false);
+        let last: Vec<_> = ReverseDecoder::new(Cursor::new(buf))
+            .unwrap()
+            .take(3)
+            .map(|entry| entry.unwrap())
+            .collect();
+        assert_eq!(
+            last,
+            [997, 998, 999]
+                .into_iter()
+                .rev()
+                .map(|i| (event(i), format!("event-{i}").into_bytes()))
+                .collect::<Entries>()
+        );
+    }
+}
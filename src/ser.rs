@@ -0,0 +1,915 @@
+use crate::json::scalar::{
+    encode_bool, encode_float, encode_integer, encode_null, encode_unsigned,
+};
+use crate::json::{TAG_EMPTY_ARRAY, TAG_EMPTY_OBJECT, flatten_str};
+use crate::{PathBuf, PrefixEncoder};
+use serde::ser::{Impossible, Serialize};
+use std::fmt::Display;
+use std::io::Write;
+
+/// Name of the struct used by `serde_json` to pass numbers with `arbitrary_precision` enabled.
+#[cfg(feature = "arbitrary_precision")]
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Serializes `value` as a stream of PEON entries written into `writer`.
+///
+/// Strings longer than `chunk_size` are split into continuation entries, the same way
+/// [crate::json::Flatten] does it.
+pub fn to_writer<W, T>(writer: W, value: &T, chunk_size: usize) -> Result<(), Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut encoder = PrefixEncoder::new(writer);
+    let mut serializer = Serializer::new(&mut encoder, chunk_size);
+    value.serialize(&mut serializer)
+}
+
+/// Serializes `value` into a byte vector of PEON entries.
+pub fn to_vec<T>(value: &T, chunk_size: usize) -> Result<Vec<u8>, Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut buf = Vec::new();
+    to_writer(&mut buf, value, chunk_size)?;
+    Ok(buf)
+}
+
+/// Serde serializer which flattens any [Serialize] type directly into PEON entries, without
+/// materializing an intermediate `serde_json::Value`.
+///
+/// Values are laid out the same way as `serde_json` would represent them: structs and maps
+/// become objects, sequences and tuples become arrays, and enum variants carrying data are
+/// written as single-key objects. The only exception are byte arrays, which are written as
+/// native bytes values (see [PrefixEncoder::write_blob]) rather than arrays of numbers.
+///
+/// Entries are streamed straight into the encoder, so struct fields are written in the order
+/// they were declared. Map members are written in the key order, just like
+/// [crate::json::Flatten] does, as their keys may come in any order: entries of each map are
+/// buffered until the whole map has been serialized.
+pub struct Serializer<'a, W> {
+    sink: Sink<'a, W>,
+    path: PathBuf<Vec<u8>>,
+    chunk_size: usize,
+}
+
+impl<'a, W: Write> Serializer<'a, W> {
+    pub fn new(encoder: &'a mut PrefixEncoder<W>, chunk_size: usize) -> Self {
+        Self {
+            sink: Sink {
+                encoder,
+                maps: Vec::new(),
+            },
+            path: PathBuf::new(Vec::new()),
+            chunk_size,
+        }
+    }
+
+    fn write_value(&mut self, value: &[u8]) -> Result<(), Error> {
+        self.sink.write_next(self.path.as_bytes(), value)
+    }
+
+    fn compound(
+        &mut self,
+        variant: Option<&str>,
+        empty_tag: u8,
+    ) -> Result<Compound<'_, 'a, W>, Error> {
+        let outer = self.path.as_bytes().len();
+        if let Some(variant) = variant {
+            self.path.push_key(variant)?;
+        }
+        let base = self.path.as_bytes().len();
+        Ok(Compound {
+            ser: self,
+            outer,
+            base,
+            len: 0,
+            empty_tag,
+            map: false,
+        })
+    }
+}
+
+impl<'s, 'a, W: Write> serde::Serializer for &'s mut Serializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'s, 'a, W>;
+    type SerializeTuple = Compound<'s, 'a, W>;
+    type SerializeTupleStruct = Compound<'s, 'a, W>;
+    type SerializeTupleVariant = Compound<'s, 'a, W>;
+    type SerializeMap = Compound<'s, 'a, W>;
+    type SerializeStruct = Compound<'s, 'a, W>;
+    type SerializeStructVariant = Compound<'s, 'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.write_value(&encode_bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.write_value(&encode_integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_i64(v as i64)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.write_value(&encode_unsigned(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.serialize_f64(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        if v.is_finite() {
+            self.write_value(&encode_float(v))
+        } else {
+            // JSON has no representation for NaN and infinities
+            self.serialize_unit()
+        }
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        let mut buf = [0u8; 4];
+        self.serialize_str(v.encode_utf8(&mut buf))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        let sink = &mut self.sink;
+        flatten_str(self.chunk_size, v, &mut self.path, |path, value| {
+            sink.write_next(path.as_bytes(), &value)
+        })
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.sink.write_blob(self.path.as_bytes(), v)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.write_value(&encode_null())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let len = self.path.as_bytes().len();
+        self.path.push_key(variant)?;
+        let result = value.serialize(&mut *self);
+        self.path.truncate(len);
+        result
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.compound(None, TAG_EMPTY_ARRAY)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.compound(None, TAG_EMPTY_ARRAY)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.compound(None, TAG_EMPTY_ARRAY)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.compound(Some(variant), TAG_EMPTY_ARRAY)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.sink.maps.push(Vec::new());
+        let mut compound = self.compound(None, TAG_EMPTY_OBJECT)?;
+        compound.map = true;
+        Ok(compound)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.compound(None, TAG_EMPTY_OBJECT)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.compound(Some(variant), TAG_EMPTY_OBJECT)
+    }
+}
+
+/// Destination of the serialized entries.
+///
+/// Entries are written straight into the encoder, unless they belong to a map which hasn't
+/// been finished yet. Such entries are buffered per member, so that the members can be sorted by
+/// their keys once the map ends.
+struct Sink<'a, W> {
+    encoder: &'a mut PrefixEncoder<W>,
+    /// Stack of unfinished maps, innermost last.
+    maps: Vec<Vec<Member>>,
+}
+
+/// Entries of a single map member, together with an encoded key segment used to sort them.
+struct Member {
+    key: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+enum Entry {
+    Value(Vec<u8>, Vec<u8>),
+    Blob(Vec<u8>, Vec<u8>),
+}
+
+impl<'a, W: Write> Sink<'a, W> {
+    fn write_next(&mut self, path: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.write(Entry::Value(path.to_vec(), value.to_vec()))
+    }
+
+    fn write_blob(&mut self, path: &[u8], bytes: &[u8]) -> Result<(), Error> {
+        self.write(Entry::Blob(path.to_vec(), bytes.to_vec()))
+    }
+
+    fn write(&mut self, entry: Entry) -> Result<(), Error> {
+        match self.maps.last_mut() {
+            Some(members) => {
+                let member = members
+                    .last_mut()
+                    .ok_or_else(|| Error::Custom("map value written without a key".into()))?;
+                member.entries.push(entry);
+            }
+            None => match entry {
+                Entry::Value(path, value) => self.encoder.write_next(&path, &value)?,
+                Entry::Blob(path, bytes) => {
+                    self.encoder.write_blob(&path, bytes.as_slice())?;
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn begin_member(&mut self, key: &[u8]) {
+        if let Some(members) = self.maps.last_mut() {
+            members.push(Member {
+                key: key.to_vec(),
+                entries: Vec::new(),
+            });
+        }
+    }
+
+    /// Pops the innermost map and writes its entries, ordered by member keys.
+    fn end_map(&mut self) -> Result<(), Error> {
+        if let Some(mut members) = self.maps.pop() {
+            // stable sort keeps the entries of duplicated keys in their original order
+            members.sort_by(|a, b| a.key.cmp(&b.key));
+            for member in members {
+                for entry in member.entries {
+                    self.write(entry)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Serializer state shared by all nested structures (sequences, maps, structs and variants).
+pub struct Compound<'s, 'a, W> {
+    ser: &'s mut Serializer<'a, W>,
+    /// Length of the path that should be restored once the structure is finished.
+    outer: usize,
+    /// Length of the path pointing to the structure itself.
+    base: usize,
+    /// Number of members written so far, which is also an index of the next sequence element.
+    len: u64,
+    /// Tag of the value written when the structure has no members.
+    empty_tag: u8,
+    /// Whether the structure is a map, whose members are buffered by the sink.
+    map: bool,
+}
+
+impl<'s, 'a, W: Write> Compound<'s, 'a, W> {
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.ser.path.truncate(self.base);
+        self.ser.path.push_index(self.len)?;
+        self.len += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.ser.path.truncate(self.base);
+        #[cfg(feature = "arbitrary_precision")]
+        if key == NUMBER_TOKEN {
+            return self.number(value);
+        }
+        self.ser.path.push_key(key)?;
+        self.len += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    /// Writes a number passed by `serde_json` with `arbitrary_precision` enabled, as a struct
+    /// holding the text of the number in a single field.
+    #[cfg(feature = "arbitrary_precision")]
+    fn number<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        use std::str::FromStr;
+
+        let text = serde_json::to_value(value).map_err(|e| Error::Custom(e.to_string()))?;
+        let number = text
+            .as_str()
+            .and_then(|text| serde_json::Number::from_str(text).ok())
+            .ok_or_else(|| Error::Custom(format!("invalid number: {text}")))?;
+        self.len += 1;
+        self.ser
+            .write_value(&crate::json::scalar::encode_number(&number))
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        if self.map {
+            self.ser.sink.end_map()?;
+        }
+        if self.len == 0 {
+            self.ser.path.truncate(self.base);
+            self.ser.write_value(&[self.empty_tag])?;
+        }
+        self.ser.path.truncate(self.outer);
+        Ok(())
+    }
+}
+
+impl<'s, 'a, W: Write> serde::ser::SerializeSeq for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'s, 'a, W: Write> serde::ser::SerializeTuple for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'s, 'a, W: Write> serde::ser::SerializeTupleStruct for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'s, 'a, W: Write> serde::ser::SerializeTupleVariant for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'s, 'a, W: Write> serde::ser::SerializeMap for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.ser.path.truncate(self.base);
+        key.serialize(MapKeySerializer {
+            path: &mut self.ser.path,
+        })?;
+        let key = &self.ser.path.as_bytes()[self.base..];
+        self.ser.sink.begin_member(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.len += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'s, 'a, W: Write> serde::ser::SerializeStruct for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'s, 'a, W: Write> serde::ser::SerializeStructVariant for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Serializer for map keys, which pushes them as key segments of the current path.
+/// Just like in JSON, only strings (and values that can be formatted as such) are allowed.
+struct MapKeySerializer<'p> {
+    path: &'p mut PathBuf<Vec<u8>>,
+}
+
+impl<'p> MapKeySerializer<'p> {
+    fn push_display<T: Display>(self, value: T) -> Result<(), Error> {
+        self.path.push_key(&value.to_string())?;
+        Ok(())
+    }
+}
+
+impl<'p> serde::Serializer for MapKeySerializer<'p> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.push_display(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.path.push_key(v)?;
+        Ok(())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("failed to write entry: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("map key must be a string")]
+    KeyMustBeAString,
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::PrefixDecoder;
+    use crate::json::Flatten;
+    use crate::path::Encode;
+    use serde::Serialize;
+    use std::cell::{Cell, RefCell};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[derive(Serialize)]
+    struct User {
+        age: u32,
+        friends: Vec<Friend>,
+        name: String,
+        nick: Option<String>,
+        role: Role,
+    }
+
+    #[derive(Serialize)]
+    struct Friend {
+        name: &'static str,
+        score: f64,
+    }
+
+    #[derive(Serialize)]
+    enum Role {
+        Admin,
+        Guest { since: i64 },
+    }
+
+    fn flattened(value: serde_json::Value, chunk_size: usize) -> Vec<u8> {
+        let mut buf = Vec::new();
+        value.flatten(chunk_size).write_to(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn serialize_same_as_flatten() {
+        let users = vec![
+            User {
+                age: 25,
+                friends: vec![Friend {
+                    name: "Bob",
+                    score: 0.5,
+                }],
+                name: "Alice".into(),
+                nick: None,
+                role: Role::Admin,
+            },
+            User {
+                age: 30,
+                friends: vec![],
+                name: "Bob".into(),
+                nick: Some("boreas".into()),
+                role: Role::Guest { since: -2 },
+            },
+        ];
+        let expected = flattened(serde_json::to_value(&users).unwrap(), 100);
+        let actual = super::to_vec(&users, 100).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    fn paths(buf: &[u8]) -> Vec<String> {
+        let mut decoder = PrefixDecoder::new(buf);
+        let mut paths = Vec::new();
+        while let Some((path, _)) = decoder.read_next().unwrap() {
+            paths.push(path.to_string());
+        }
+        paths
+    }
+
+    #[test]
+    fn serialize_unsorted_struct() {
+        #[derive(Serialize)]
+        struct Unsorted {
+            z: u32,
+            a: Vec<Unsorted>,
+            m: Role,
+            b: serde_json::Value,
+        }
+
+        let value = Unsorted {
+            z: 1,
+            a: vec![Unsorted {
+                z: 2,
+                a: vec![],
+                m: Role::Guest { since: 3 },
+                b: serde_json::json!({ "y": [1, 2], "x": "x" }),
+            }],
+            m: Role::Admin,
+            b: serde_json::Value::Null,
+        };
+        // fields are written in declaration order, members of maps in key order
+        let actual = super::to_vec(&value, 10).unwrap();
+        assert_eq!(
+            paths(&actual),
+            [
+                "$.z",
+                "$.a[0].z",
+                "$.a[0].a",
+                "$.a[0].m.Guest.since",
+                "$.a[0].b.x",
+                "$.a[0].b.y[0]",
+                "$.a[0].b.y[1]",
+                "$.m",
+                "$.b",
+            ]
+        );
+
+        let value = HashMap::from([("b", vec![2]), ("c", vec![3]), ("a", vec![1])]);
+        let expected = flattened(serde_json::to_value(&value).unwrap(), 10);
+        let actual = super::to_vec(&value, 10).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn serialize_streams_struct_fields() {
+        /// Element recording how many bytes reached the writer before it was serialized.
+        struct Probe<'a>(&'a Cell<usize>, &'a RefCell<Vec<usize>>);
+
+        impl Serialize for Probe<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.1.borrow_mut().push(self.0.get());
+                serializer.serialize_str("event")
+            }
+        }
+
+        struct Counter<'a>(&'a Cell<usize>);
+
+        impl std::io::Write for Counter<'_> {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.set(self.0.get() + buf.len());
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        #[derive(Serialize)]
+        struct Log<'a> {
+            name: &'static str,
+            events: Vec<Probe<'a>>,
+        }
+
+        let written = Cell::new(0);
+        let seen = RefCell::new(Vec::new());
+        let log = Log {
+            name: "log",
+            events: (0..1000).map(|_| Probe(&written, &seen)).collect(),
+        };
+        super::to_writer(Counter(&written), &log, 100).unwrap();
+        // every element has been written before the next one was serialized, so the encoded
+        // vector is never held in memory
+        let seen = seen.into_inner();
+        assert_eq!(seen.len(), 1000);
+        assert!(seen.windows(2).all(|w| w[0] < w[1]), "{seen:?}");
+    }
+
+    #[cfg(feature = "arbitrary_precision")]
+    #[test]
+    fn serialize_arbitrary_precision() {
+        let value: serde_json::Value =
+            serde_json::from_str(r#"{ "a": 0.10000000000000000001, "b": [1, 2.5] }"#).unwrap();
+        let expected = flattened(value.clone(), 100);
+        let actual = super::to_vec(&value, 100).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn serialize_chunked_string() {
+        let value = BTreeMap::from([("description", "lorem ipsum dolor sit amet ".repeat(10))]);
+        let expected = flattened(serde_json::to_value(&value).unwrap(), 40);
+        let actual = super::to_vec(&value, 40).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn serialize_non_string_keys() {
+        let value = BTreeMap::from([(1u32, true), (2, false)]);
+        let expected = flattened(serde_json::json!({ "1": true, "2": false }), 100);
+        let actual = super::to_vec(&value, 100).unwrap();
+        assert_eq!(actual, expected);
+
+        let value = BTreeMap::from([(vec![1u8], true)]);
+        let res = super::to_vec(&value, 100);
+        assert!(matches!(res, Err(super::Error::KeyMustBeAString)));
+    }
+
+    #[test]
+    fn serialize_empty_containers() {
+        #[derive(Serialize)]
+        struct Empty {}
+
+        let value = (
+            Vec::<u8>::new(),
+            BTreeMap::<String, u8>::new(),
+            Empty {},
+            vec![()],
+        );
+        let expected = flattened(serde_json::to_value(&value).unwrap(), 100);
+        let actual = super::to_vec(&value, 100).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn serialize_scalar_root() {
+        let expected = flattened(serde_json::json!(-42), 100);
+        let actual = super::to_vec(&-42i32, 100).unwrap();
+        assert_eq!(actual, expected);
+    }
+}
@@ -0,0 +1,388 @@
+use crate::checksum::{BlockChecksum, EXT_CHECKSUM};
+use crate::compression::EXT_COMPRESSED;
+use crate::encoding::{EXT_ENTRY, MAX_PATH_LEN, split_header};
+use crate::header::EXT_HEADER;
+use crate::index::EXT_INDEX_TRAILER;
+use crate::{DecoderLimits, Error, Extension, Path, StreamHeader};
+
+/// Decoder of entries written by [crate::PrefixEncoder], reading them from a byte slice.
+///
+/// Unlike [crate::PrefixDecoder], it doesn't copy values: they're borrowed directly from the
+/// input, only keys are reconstructed into a buffer of the decoder. Checksums are verified and
+/// [DecoderLimits] are enforced the same way. Compressed blocks aren't supported, as their
+/// entries can't be borrowed from the input, so streams written with
+/// [crate::PrefixEncoder::with_compression] fail with [Error::UnsupportedExtension].
+pub struct SliceDecoder<'a> {
+    buf: &'a [u8],
+    /// Position of the next item within `buf`.
+    pos: usize,
+    last_key: Vec<u8>,
+    limits: DecoderLimits,
+    /// Number of entries read so far.
+    entries: u64,
+    header: Option<StreamHeader>,
+    /// Index of the current checksummed block.
+    block: u64,
+    /// Position of the current checksummed block within `buf`.
+    block_start: usize,
+    /// Position of the last read entry within `buf`.
+    entry_pos: usize,
+    /// Ids of registered extension kinds.
+    extensions: Vec<u16>,
+}
+
+impl<'a> SliceDecoder<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self::with_limits(buf, DecoderLimits::default())
+    }
+
+    pub fn with_limits(buf: &'a [u8], limits: DecoderLimits) -> Self {
+        Self {
+            buf,
+            pos: 0,
+            last_key: Vec::new(),
+            limits,
+            entries: 0,
+            header: None,
+            block: 0,
+            block_start: 0,
+            entry_pos: 0,
+            extensions: Vec::new(),
+        }
+    }
+
+    /// Makes the decoder skip mandatory extension entries of kind `E` instead of failing, see
+    /// [crate::PrefixDecoder::register_extension].
+    pub fn register_extension<E: Extension>(&mut self) {
+        if !self.extensions.contains(&E::ID) {
+            self.extensions.push(E::ID);
+        }
+    }
+
+    /// Reads the stream header, see [crate::PrefixDecoder::read_stream_header].
+    pub fn read_stream_header(&mut self) -> Result<StreamHeader, Error> {
+        if self.pos == 0
+            && let Some(header_buf) = self.buf.first_chunk::<6>()
+        {
+            let (key_len, value_len, prefix_len) = split_header(header_buf);
+            if header_buf[0] & EXT_ENTRY != 0 && (prefix_len & MAX_PATH_LEN) as u16 == EXT_HEADER {
+                self.pos = 6;
+                self.read_header_ext(key_len & MAX_PATH_LEN, value_len)?;
+            }
+        }
+        self.header.ok_or(Error::MissingHeader)
+    }
+
+    /// Returns the stream header, if the stream started with one.
+    pub fn header(&self) -> Option<StreamHeader> {
+        self.header
+    }
+
+    /// Returns the position of the header of the last read entry within the input.
+    pub fn entry_position(&self) -> usize {
+        self.entry_pos
+    }
+
+    fn read_header_ext(&mut self, key_len: usize, value_len: usize) -> Result<(), Error> {
+        self.take(key_len)?;
+        self.header = Some(StreamHeader::decode(self.take(value_len)?)?);
+        // checksummed blocks start right after the header
+        self.block_start = self.pos;
+        Ok(())
+    }
+
+    /// Verifies the checksum of the block ending at `pos`.
+    fn read_checksum_ext(&mut self, payload: &[u8], pos: usize) -> Result<(), Error> {
+        let checksum = BlockChecksum::decode(payload)?;
+        let crc = crc32c::crc32c(&self.buf[self.block_start..pos]);
+        if checksum.block != self.block || checksum.crc != crc {
+            return Err(Error::ChecksumMismatch { block: self.block });
+        }
+        self.block += 1;
+        self.block_start = self.pos;
+        Ok(())
+    }
+
+    /// Consumes `len` bytes of the input.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let buf = self.buf;
+        let bytes = buf.get(self.pos..self.pos + len).ok_or(Error::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn read_next(&mut self) -> Result<Option<(Path<'_>, &'a [u8])>, Error> {
+        let mut skipped = 0;
+        loop {
+            let pos = self.pos;
+            if pos == self.buf.len() {
+                // No more entries to read
+                if pos > self.block_start
+                    && self
+                        .header
+                        .is_some_and(|h| h.has_flag(StreamHeader::FLAG_CHECKSUMS))
+                {
+                    return Err(Error::MissingChecksum { block: self.block });
+                }
+                return Ok(None);
+            }
+            let header_buf = self.take(6)?.first_chunk::<6>().unwrap();
+            let (key_len, value_len, prefix_len) = split_header(header_buf);
+
+            if header_buf[0] & EXT_ENTRY != 0 {
+                let id = (prefix_len & MAX_PATH_LEN) as u16;
+                if id == EXT_HEADER && pos == 0 {
+                    self.read_header_ext(key_len & MAX_PATH_LEN, value_len)?;
+                    continue;
+                }
+                let optional = header_buf[4] & EXT_ENTRY != 0;
+                if id == EXT_COMPRESSED || !optional && !self.extensions.contains(&id) {
+                    return Err(Error::UnsupportedExtension(id));
+                }
+                if skipped == self.limits.max_extension_skips {
+                    return Err(Error::TooManyExtensions(skipped));
+                }
+                skipped += 1;
+                // key part of extension entries is reserved for future use
+                self.take(key_len & MAX_PATH_LEN)?;
+                let payload = self.take(value_len)?;
+                if id == EXT_CHECKSUM {
+                    self.read_checksum_ext(payload, pos)?;
+                } else if id == EXT_INDEX_TRAILER && pos == self.block_start {
+                    // trailer follows the last checksum, it's not a part of any block
+                    self.block_start = self.pos;
+                }
+                continue;
+            }
+
+            if key_len > self.limits.max_key_len {
+                return Err(Error::KeyTooLong(key_len));
+            }
+            if value_len > self.limits.max_value_len {
+                return Err(Error::ValueTooLong(value_len));
+            }
+            if self.entries == self.limits.max_entries {
+                return Err(Error::TooManyEntries(self.entries));
+            }
+            if prefix_len > key_len || prefix_len > self.last_key.len() {
+                return Err(Error::PrefixOutOfRange {
+                    prefix_len,
+                    last_len: self.last_key.len(),
+                    key_len,
+                });
+            }
+
+            let suffix = self.take(key_len - prefix_len)?;
+            let value = self.take(value_len)?;
+            self.last_key.truncate(prefix_len);
+            self.last_key.extend_from_slice(suffix);
+            self.entries += 1;
+            self.entry_pos = pos;
+            return Ok(Some((Path::from_slice(&self.last_key), value)));
+        }
+    }
+}
+
+/// Stream memory-mapped from a file, so that [SliceDecoder] can decode it without reading it
+/// into memory first.
+#[cfg(feature = "mmap")]
+pub struct MappedFile {
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedFile {
+    /// Maps the whole `file` into memory.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, nor truncated, while it's mapped, see
+    /// [memmap2::Mmap::map].
+    pub unsafe fn new(file: &std::fs::File) -> std::io::Result<Self> {
+        let map = unsafe { memmap2::Mmap::map(file)? };
+        Ok(Self { map })
+    }
+
+    pub fn decoder(&self) -> SliceDecoder<'_> {
+        SliceDecoder::new(&self.map)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SliceDecoder;
+    use crate::checksum::EXT_CHECKSUM;
+    use crate::json::Flatten;
+    use crate::{Error, Extension, MIN_USER_ID, PrefixDecoder, PrefixEncoder, StreamHeader};
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    type Entries = Vec<(Vec<u8>, Vec<u8>)>;
+
+    fn sample() -> Vec<u8> {
+        let value: serde_json::Value =
+            serde_json::from_str(include_str!("../assets/complex.json")).unwrap();
+        let header = StreamHeader::new(StreamHeader::FLAG_CHECKSUMS);
+        let mut encoder = PrefixEncoder::with_header(Vec::new(), header)
+            .unwrap()
+            .with_index(10)
+            .with_checksums(7);
+        for (path, value) in value.flatten(64) {
+            encoder.write_next(path.as_bytes(), &value).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn decode_slice(buf: &[u8]) -> Result<Entries, Error> {
+        let mut decoder = SliceDecoder::new(buf);
+        let mut entries = Vec::new();
+        while let Some((path, value)) = decoder.read_next()? {
+            entries.push((path.as_bytes().to_vec(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    fn decode_reader(buf: &[u8]) -> Result<Entries, Error> {
+        let mut decoder = PrefixDecoder::new(buf);
+        let mut entries = Vec::new();
+        while let Some((path, value)) = decoder.read_next()? {
+            entries.push((path.as_bytes().to_vec(), value.to_vec()));
+        }
+        Ok(entries)
+    }
+
+    #[test]
+    fn slice_decoder_round_trip() {
+        let buf = sample();
+        let expected = decode_reader(&buf).unwrap();
+        assert_eq!(decode_slice(&buf).unwrap(), expected);
+
+        let mut decoder = SliceDecoder::new(&buf);
+        assert_eq!(
+            decoder.read_stream_header().unwrap(),
+            StreamHeader::new(StreamHeader::FLAG_CHECKSUMS)
+        );
+        while let Some((_, value)) = decoder.read_next().unwrap() {
+            // values are borrowed from the input
+            assert!(buf.as_ptr_range().contains(&value.as_ptr()) || value.is_empty());
+        }
+        assert!(matches!(
+            SliceDecoder::new(&buf[6..]).read_stream_header(),
+            Err(Error::MissingHeader)
+        ));
+    }
+
+    #[test]
+    fn slice_decoder_malformed() {
+        let buf = sample();
+        // last byte of the last block, i.e. of the index payload
+        let checksum = buf
+            .windows(6)
+            .rposition(|w| w == [0x80, 0, 0, 12, 0x80, EXT_CHECKSUM as u8])
+            .unwrap();
+        let mut corrupted = buf.clone();
+        corrupted[checksum - 1] ^= 0x10;
+        let res = decode_slice(&corrupted);
+        assert!(
+            matches!(res, Err(Error::ChecksumMismatch { .. })),
+            "{res:?}"
+        );
+
+        let res = decode_slice(&buf[..buf.len() - 3]);
+        assert!(matches!(res, Err(Error::Truncated)), "{res:?}");
+
+        // compressed blocks are mandatory extension entries
+        let compressed = [0x80, 0, 0, 3, 0, 6, 0, 0, 0];
+        let res = decode_slice(&compressed);
+        assert!(
+            matches!(res, Err(Error::UnsupportedExtension(6))),
+            "{res:?}"
+        );
+    }
+
+    struct Schema;
+
+    impl Extension for Schema {
+        const ID: u16 = MIN_USER_ID;
+        const OPTIONAL: bool = false;
+
+        fn encode(&self, buf: &mut Vec<u8>) {
+            buf.extend_from_slice(b"v1");
+        }
+
+        fn decode(_payload: &[u8]) -> Result<Self, Error> {
+            Ok(Schema)
+        }
+    }
+
+    #[test]
+    fn slice_decoder_registered_extension() {
+        let mut encoder = PrefixEncoder::new(Vec::new());
+        encoder.write_extension(&Schema).unwrap();
+        encoder.write_next(&[0, b'a'], b"a").unwrap();
+        let buf = encoder.finish().unwrap();
+
+        let res = decode_slice(&buf);
+        assert!(
+            matches!(res, Err(Error::UnsupportedExtension(Schema::ID))),
+            "{res:?}"
+        );
+
+        // mandatory extensions are skipped once registered
+        let mut decoder = SliceDecoder::new(&buf);
+        decoder.register_extension::<Schema>();
+        let (path, value) = decoder.read_next().unwrap().unwrap();
+        assert_eq!(path.to_string(), "$.a");
+        assert_eq!(value, b"a");
+        assert!(decoder.read_next().unwrap().is_none());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn slice_decoder_mapped_file() {
+        use std::io::Write;
+
+        let buf = sample();
+        let path = std::env::temp_dir().join(format!("peon-mmap-{}", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(&buf)
+            .unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        // SAFETY: the file is private to this test
+        let mapped = unsafe { super::MappedFile::new(&file) }.unwrap();
+        let mut decoder = mapped.decoder();
+        let mut count = 0;
+        while decoder.read_next().unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, decode_reader(&buf).unwrap().len());
+        drop(mapped);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    proptest! {
+        #[test]
+        fn slice_decoder_agrees_with_reader(
+            flips in vec((any::<usize>(), any::<u8>()), 0..4),
+            cut: usize,
+        ) {
+            let mut buf = sample();
+            for (pos, byte) in flips {
+                let len = buf.len();
+                buf[pos % len] ^= byte;
+            }
+            buf.truncate(cut % (buf.len() + 1));
+            if let Ok(entries) = decode_slice(&buf) {
+                prop_assert_eq!(entries, decode_reader(&buf).unwrap());
+            }
+        }
+    }
+}